@@ -1,327 +1,1077 @@
 use std::sync::Arc;
 
-use chrono::Datelike;
+use chrono::{DateTime, Datelike};
+use chrono_tz::Tz;
 use serde::Serialize;
 use tokio::sync::{mpsc::UnboundedReceiver, Mutex};
 
 use crate::{
-    consts::EVENTS_SHEET_CSV,
-    csv::{load_csv, parse_csv, write_csv},
-    discord, req, Result,
+    changes,
+    channels::{
+        load_channels, save_channels, CategoryStyle, ChannelConfig, ChannelSetting, QuietWeek,
+    },
+    clock::Clock,
+    consts::{CATCH_UP_GRACE_HOURS, LOGO_URL},
+    corrections,
+    discord::{self, MessageComponent},
+    events::{load_announcements, Event},
+    reminders::run_reminders,
+    req, rsvp, scheduled,
+    template::DigestVars,
+    Result,
 };
 
+#[allow(clippy::enum_variant_names)]
 pub enum AnnouncerCommand {
-    RegisterChannel(discord::Snowflake),
+    RegisterChannel(discord::Snowflake, Vec<ChannelSetting>),
+    /// Changes the settings of a registered channel, ignoring unknown ones.
+    UpdateChannel(discord::Snowflake, Vec<ChannelSetting>),
     UnregisterChannel(discord::Snowflake),
 }
 
-const CHANNELS_CSV: &str = "channels.csv";
-
-struct Event {
-    name: String,
-    date: String,
-    location: String,
-    category: Option<String>,
-    attending: Option<String>,
-    notes: Option<String>,
+#[derive(Default, Serialize)]
+pub struct CreateMessageRequest {
+    pub content: Option<String>,
+    pub nonce: Option<String>,
+    pub tts: Option<bool>,
+    pub embeds: Option<Vec<discord::Embed>>,
+    pub allowed_mentions: Option<discord::AllowedMentions>,
+    pub message_reference: Option<discord::MessageReference>,
+    pub components: Option<Vec<discord::MessageComponent>>,
+    pub sticker_ids: Option<Vec<discord::Snowflake>>,
+    pub payload_json: Option<String>,
+    pub attachments: Option<Vec<discord::Attachment>>,
+    pub flags: Option<discord::MessageFlags>,
 }
 
-impl Event {
-    fn start_time(&self) -> Option<chrono::DateTime<chrono::Local>> {
-        let parts: Vec<&str> = self.date.split(' ').collect();
-        let day = parts.first();
-        let month = parts.get(1);
-        let year = parts.get(2);
-
-        let (Some(d), Some(m), Some(y)) = (day, month, year) else {
-            return None;
-        };
-
-        let day: u32 = if d.contains('-') {
-            if let Some((from, _to)) = d.split_once('-') {
-                from.parse().ok()?
-            } else {
-                return None;
-            }
-        } else {
-            d.parse().ok()?
-        };
-
-        let month: chrono::Month = m.parse().ok()?;
-        let year: i32 = y.parse().ok()?;
-
-        let naive = chrono::NaiveDate::from_ymd_opt(year, month.number_from_month(), day)?;
-        let naive = naive.and_time(chrono::NaiveTime::MIN);
-        let date = chrono::TimeZone::from_local_datetime(&chrono::Local, &naive);
-        date.single()
-    }
-
-    fn date_string(&self) -> String {
-        if let Some(dt) = self.start_time() {
-            dt.format("%A %d %b").to_string()
-        } else {
-            self.date.clone()
-        }
-    }
+pub async fn send_message(
+    body: CreateMessageRequest,
+    channel: &discord::Snowflake,
+) -> Result<discord::Message> {
+    let uri = req::api_uri(format!("/channels/{channel}/messages"));
+    req::post(uri, body).await
 }
 
-async fn load_announcements() -> Result<Vec<Event>> {
-    let csv = req::get(EVENTS_SHEET_CSV).await?;
-    let data = parse_csv(&csv)?;
-    let mut events = Vec::new();
-    for row in data.into_iter().skip(2) {
-        let name = row.first();
-        let date = row.get(1);
-        let location = row.get(2);
-        let category = row.get(3);
-        let attending = row.get(4);
-        let notes = row.get(5);
-
-        if let (Some(name), Some(date), Some(location)) = (name, date, location) {
-            events.push(Event {
-                name: name.clone(),
-                date: date.clone(),
-                location: location.clone(),
-                category: category.cloned(),
-                attending: attending.cloned(),
-                notes: notes.cloned(),
-            })
-        }
+/// Send a direct message to a user, opening the DM channel if need be.
+pub async fn send_dm(
+    body: CreateMessageRequest,
+    user: &discord::Snowflake,
+) -> Result<discord::Message> {
+    #[derive(Serialize)]
+    struct CreateDmRequest<'a> {
+        recipient_id: &'a discord::Snowflake,
     }
 
-    // TODO remove once satisfied with testing.
-    events.push(Event {
-        name: "Test Event 1".to_string(),
-        date: "02 Feb 2024".to_string(),
-        location: "Location 1".to_string(),
-        category: None,
-        attending: None,
-        notes: Some("Notes for event".to_string()),
-    });
-    events.push(Event {
-        name: "Test Event 2".to_string(),
-        date: "03 Feb 2024".to_string(),
-        location: "Location 2".to_string(),
-        category: None,
-        attending: None,
-        notes: Some("Notes for event".to_string()),
-    });
-
-    Ok(events)
+    let uri = req::api_uri("/users/@me/channels");
+    let channel: discord::Channel = req::post(uri, CreateDmRequest { recipient_id: user }).await?;
+    send_message(body, channel.id()).await
 }
 
-async fn send_embed(
+pub async fn send_embed(
     embed: discord::Embed,
     channel: &discord::Snowflake,
 ) -> Result<discord::Message> {
-    #[derive(Default, Serialize)]
-    struct CreateMessageRequest {
-        content: Option<String>,
-        nonce: Option<String>,
-        tts: Option<bool>,
-        embeds: Option<Vec<discord::Embed>>,
-        allowed_mentions: Option<discord::AllowedMentions>,
-        message_reference: Option<discord::MessageReference>,
-        components: Option<Vec<discord::MessageComponent>>,
-        sticker_ids: Option<Vec<discord::Snowflake>>,
-        payload_json: Option<String>,
-        attachments: Option<Vec<discord::Attachment>>,
-        flags: Option<i32>,
-    }
-
-    let uri = req::api_uri(format!("/channels/{channel}/messages"));
     let body = CreateMessageRequest {
         embeds: Some(vec![embed]),
         ..Default::default()
     };
-    req::post(uri, body).await
+    send_message(body, channel).await
 }
 
-async fn announce(channels: &[discord::Snowflake]) {
-    const DATE_FORMAT: &str = "%A %d/%m";
-
-    let mut events = match load_announcements().await {
-        Ok(events) => events,
-        Err(e) => {
-            eprintln!("Failed to load events: {e}");
-            return;
-        }
+/// Events within the channel's window, and those later in the month if the
+/// channel teases them, in order of start time. If the channel groups by
+/// category, events in the window are grouped in order of each category's
+/// first event.
+pub fn upcoming(
+    mut events: Vec<Event>,
+    now: &DateTime<Tz>,
+    channel: &ChannelConfig,
+) -> (Vec<Event>, Vec<Event>) {
+    let tz = now.timezone();
+    let today = now.date_naive();
+    let Some(last_day) = channel.window.last_day(today) else {
+        return (Vec::new(), Vec::new());
     };
 
-    let now = chrono::Local::now();
-
-    // Filter for events in coming week.
+    // Drop past events, those in categories the channel doesn't announce and
+    // those too far ahead to be worth a mention.
     events.retain(|e| {
-        if let Some(start) = e
-            .start_time()
-            .and_then(|t| t.signed_duration_since(now).to_std().ok())
-        {
-            start < std::time::Duration::from_secs(60 * 60 * 24 * 7 + 60 * 60 * 15)
-        } else {
-            false
-        }
+        e.start_time(&tz).is_some_and(|start| {
+            channel.filter.matches(e)
+                && start >= *now
+                && (start.date_naive() <= last_day
+                    || (channel.teaser
+                        && start.year() == today.year()
+                        && start.month() == today.month()))
+        })
+    });
+
+    events.sort_by_key(|e| e.start_time(&tz));
+
+    // Split off events after the window to tease at the end.
+    let (mut events, later): (Vec<Event>, Vec<Event>) = events.into_iter().partition(|e| {
+        e.start_time(&tz)
+            .is_some_and(|t| t.date_naive() <= last_day)
     });
 
+    if channel.group_by_category {
+        let mut order: Vec<CategoryStyle> = Vec::new();
+        for event in &events {
+            let style = channel.category_style(event);
+            if !order.iter().any(|s| s.matches(&style.name)) {
+                order.push(style);
+            }
+        }
+        events.sort_by_key(|e| {
+            let style = channel.category_style(e);
+            order.iter().position(|s| s.matches(&style.name))
+        });
+    }
+
+    (events, later)
+}
+
+/// Embeds of the digest of upcoming events, empty if there are none.
+pub fn digest(
+    events: Vec<Event>,
+    now: &DateTime<Tz>,
+    channel: &ChannelConfig,
+) -> Vec<discord::Embed> {
+    let (events, later) = upcoming(events, now, channel);
+    digest_pages(&events, &later, now, channel)
+        .into_iter()
+        .flat_map(|(embeds, _)| embeds)
+        .collect()
+}
+
+/// An embed in the style of the digest, with the footer and logo.
+fn digest_page(title: String, description: String) -> discord::Embed {
+    const FOOTER: &str = "Reply /events for more";
+
+    let embed = discord::Embed::new(title, description).footer(FOOTER);
+    match LOGO_URL {
+        Some(logo) => embed.thumbnail(logo),
+        None => embed,
+    }
+}
+
+/// Messages listing events in the window, followed by those later in the
+/// month, split within Discord's limits and with at most one event for each
+/// row of RSVP buttons a message can have. Each message's embeds are paired
+/// with the events they have fields for, in order.
+pub fn digest_pages<'a>(
+    events: &'a [Event],
+    later: &[Event],
+    now: &DateTime<Tz>,
+    channel: &ChannelConfig,
+) -> Vec<(Vec<discord::Embed>, &'a [Event])> {
+    let tz = now.timezone();
+    let Some(vars) = DigestVars::new(&channel.window, now, events.len()) else {
+        return Vec::new();
+    };
     if events.is_empty() {
-        return;
+        return Vec::new();
     }
 
-    let desc = if let Some(end) = now.checked_add_days(chrono::Days::new(7)) {
-        format!(
-            "{} through {}",
-            now.format(DATE_FORMAT),
-            end.format(DATE_FORMAT)
-        )
-    } else {
-        format!("Week beginning {}", now.format(DATE_FORMAT))
+    let title = channel.template.title(&vars);
+    let desc = channel.template.description(&vars);
+    // When grouping, each category has its own embed headed with its style.
+    let style = |event: &Event| {
+        channel
+            .group_by_category
+            .then(|| channel.category_style(event))
     };
-    let mut embed = discord::Embed::new("Events this Week", &desc);
-
-    for event in events {
-        let date = event.date_string();
-        let notes = if let Some(notes) = event.notes {
-            format!(". {notes}")
-        } else {
-            String::new()
+    let page = |style: Option<&CategoryStyle>, continued: bool| {
+        let mut heading = match style {
+            Some(style) => format!("{title}: {}", style.heading()),
+            None => title.clone(),
         };
-        embed.add_field(event.name, format!("{}, {}{}", date, event.location, notes));
-    }
+        if continued {
+            heading.push_str(" (continued)");
+        }
+        let embed = digest_page(heading, desc.clone());
+        match style.and_then(|style| style.colour) {
+            Some(colour) => embed.colour(colour),
+            None => embed,
+        }
+    };
+    // Later categories in the same message are headed with just their style.
+    let section = |style: &CategoryStyle| {
+        let embed = discord::Embed::new(style.heading(), String::new());
+        match style.colour {
+            Some(colour) => embed.colour(colour),
+            None => embed,
+        }
+    };
+    // Room is kept for the RSVP counts later added to each event's field.
+    let fits = |embeds: &[discord::Embed],
+                embed: &discord::Embed,
+                (name, value): (&str, &str),
+                listed: usize| {
+        let length: usize = embeds.iter().map(discord::Embed::length).sum();
+        embed.has_room(name, value)
+            && length
+                + embed.length()
+                + discord::Embed::field_length(name, value)
+                + listed * rsvp::MAX_COUNTS_LENGTH
+                <= discord::Embed::MAX_LENGTH
+    };
 
-    embed.add_field(String::new(), "@everyone".to_string());
+    let mut pages = Vec::new();
+    let mut embeds = Vec::new();
+    let mut current = style(&events[0]);
+    let mut embed = page(current.as_ref(), false);
+    let mut first = 0;
+    for (i, event) in events.iter().enumerate() {
+        let (name, value) = channel.template.field(event, &tz);
+        let event_style = style(event);
+        let full = i - first == MessageComponent::MAX_ROWS;
+        // Categories differing only in case are listed together.
+        let new_category = event_style
+            .as_ref()
+            .filter(|style| current.as_ref().is_some_and(|c| !c.matches(&style.name)));
+        if let Some(style) = new_category {
+            embeds.push(embed);
+            embed = section(style);
+            if full
+                || embeds.len() == discord::Embed::MAX_EMBEDS
+                || !fits(&embeds, &embed, (&name, &value), i - first + 1)
+            {
+                pages.push((std::mem::take(&mut embeds), &events[first..i]));
+                embed = page(Some(style), false);
+                first = i;
+            }
+            current = event_style;
+        } else if full || !fits(&embeds, &embed, (&name, &value), i - first + 1) {
+            embeds.push(embed);
+            pages.push((std::mem::take(&mut embeds), &events[first..i]));
+            embed = page(current.as_ref(), true);
+            first = i;
+        }
+        embed.add_field(name, value);
+    }
 
-    for channel in channels {
-        if let Err(e) = send_embed(embed.clone(), channel).await {
-            eprintln!("{e}");
+    if !later.is_empty() {
+        let teasers: Vec<String> = later
+            .iter()
+            .map(|event| format!("{}, {}", event.name, event.date_string(&tz)))
+            .collect();
+        let teasers = teasers.join("\n");
+        if !fits(
+            &embeds,
+            &embed,
+            ("Later this Month", &teasers),
+            events.len() - first,
+        ) {
+            embeds.push(embed);
+            pages.push((std::mem::take(&mut embeds), &events[first..]));
+            embed = page(None, true);
+            first = events.len();
         }
+        embed.add_field("Later this Month".to_string(), teasers);
     }
-}
 
-async fn load_channels() -> Vec<discord::Snowflake> {
-    let csv = if let Ok(csv) = load_csv(CHANNELS_CSV).await {
-        csv
-    } else {
-        Vec::new()
-    };
+    embeds.push(embed);
+    pages.push((embeds, &events[first..]));
+    pages
+}
 
-    csv.into_iter()
-        .filter_map(|row| row.into_iter().next())
-        .collect()
+/// The first event after the channel's window.
+fn next_event<'a>(
+    events: &'a [Event],
+    now: &DateTime<Tz>,
+    channel: &ChannelConfig,
+) -> Option<&'a Event> {
+    let tz = now.timezone();
+    let last_day = channel.window.last_day(now.date_naive())?;
+    events
+        .iter()
+        .filter(|e| channel.filter.matches(e))
+        .filter_map(|e| e.start_time(&tz).map(|start| (start, e)))
+        .filter(|(start, _)| start.date_naive() > last_day)
+        .min_by_key(|(start, _)| *start)
+        .map(|(_, e)| e)
 }
 
-async fn save_channels(channels: &[discord::Snowflake]) {
-    if let Err(e) = write_csv(
-        &channels.iter().map(|id| vec![id.clone()]).collect(),
-        CHANNELS_CSV,
-    )
-    .await
-    {
-        eprintln!("{e}");
+/// The channel's message for a window without events, or None if the channel
+/// stays silent.
+pub fn quiet_digest(
+    events: &[Event],
+    now: &DateTime<Tz>,
+    channel: &ChannelConfig,
+) -> Option<discord::Embed> {
+    if channel.quiet_week == QuietWeek::Silent {
+        return None;
+    }
+
+    let vars = DigestVars::new(&channel.window, now, 0)?;
+    let mut embed = digest_page(channel.template.title(&vars), channel.template.empty(&vars));
+    if channel.quiet_week == QuietWeek::NextEvent {
+        if let Some(event) = next_event(events, now, channel) {
+            let (name, value) = channel.template.field(event, &now.timezone());
+            embed.add_field(format!("Next up: {name}"), value);
+        }
     }
+    Some(embed)
 }
 
-async fn register_channel(channel: discord::Snowflake) -> Result<Vec<discord::Snowflake>> {
-    let mut registered = load_channels().await;
+/// Send the announcement for `slot` to the channel. Returns false if it
+/// failed and should be retried.
+async fn announce(channel: &ChannelConfig, events: &[Event], slot: &DateTime<Tz>) -> bool {
+    let (upcoming, later) = upcoming(events.to_vec(), slot, channel);
+    let mut pages = digest_pages(&upcoming, &later, slot, channel);
 
-    if !registered.contains(&channel) {
-        registered.push(channel);
-        save_channels(&registered).await;
+    // Nobody is pinged about a quiet week.
+    let quiet = pages.is_empty();
+    if quiet {
+        pages.extend(quiet_digest(events, slot, channel).map(|embed| (vec![embed], &[][..])));
     }
 
-    Ok(registered)
+    // Each page is its own message, so that its RSVP buttons line up with its
+    // fields. Pages are recorded as they're sent, so that a retry carries on
+    // from the first page which failed. Only the first pings.
+    let sent = corrections::posted_pages(&channel.id, slot).await;
+    for (i, (embeds, page_events)) in pages.into_iter().enumerate().skip(sent) {
+        let body = CreateMessageRequest {
+            content: channel.ping.content().filter(|_| i == 0 && !quiet),
+            allowed_mentions: Some(if quiet {
+                discord::AllowedMentions::none()
+            } else {
+                channel.ping.allowed_mentions()
+            }),
+            embeds: Some(embeds.clone()),
+            components: Some(rsvp::buttons(page_events)),
+            ..Default::default()
+        };
+        match send_message(body, &channel.id).await {
+            Ok(message) => {
+                corrections::record(
+                    &channel.id,
+                    slot,
+                    message.id().clone(),
+                    &embeds,
+                    page_events,
+                )
+                .await
+            }
+            Err(e) => {
+                eprintln!("{e}");
+                return false;
+            }
+        }
+    }
+    true
 }
 
-async fn unregister_channel(channel: &discord::Snowflake) -> Result<Vec<discord::Snowflake>> {
-    let mut registered = load_channels().await;
+/// Send any announcements which are due or were missed within the grace
+/// window, saving each channel as soon as it has been announced to. The
+/// channels aren't locked while messages are sent. Returns true if any failed
+/// and should be retried.
+async fn catch_up(channels: &Mutex<Vec<ChannelConfig>>, now: &DateTime<Tz>) -> bool {
+    let grace = chrono::Duration::hours(CATCH_UP_GRACE_HOURS);
+    let due: Vec<(ChannelConfig, DateTime<Tz>)> = channels
+        .lock()
+        .await
+        .iter()
+        .filter_map(|channel| {
+            missed_announcement(channel.last_announced, now, grace)
+                .map(|slot| (channel.clone(), slot))
+        })
+        .collect();
 
-    if registered.contains(channel) {
-        registered.retain(|id| id != channel);
-        save_channels(&registered).await;
+    if due.is_empty() {
+        return false;
     }
 
-    Ok(registered)
+    let events = match load_announcements().await {
+        Ok(events) => events,
+        Err(e) => {
+            eprintln!("Failed to load events: {e}");
+            return true;
+        }
+    };
+
+    let mut failed = false;
+    for (channel, slot) in due {
+        if !announce(&channel, &events, &slot).await {
+            failed = true;
+            continue;
+        }
+
+        let mut lock = channels.lock().await;
+        if let Some(channel) = lock.iter_mut().find(|c| c.id == channel.id) {
+            channel.last_announced = Some(slot.date_naive());
+        }
+        save_channels(&lock).await;
+    }
+    failed
 }
 
-async fn handle_command(command: AnnouncerCommand) -> Option<Vec<discord::Snowflake>> {
+fn handle_command(
+    command: AnnouncerCommand,
+    channels: &mut Vec<ChannelConfig>,
+    now: &DateTime<Tz>,
+) -> bool {
     match command {
-        AnnouncerCommand::RegisterChannel(id) => {
-            if let Ok(registered) = register_channel(id).await {
-                Some(registered)
+        AnnouncerCommand::RegisterChannel(id, settings) => {
+            if let Some(channel) = channels.iter_mut().find(|channel| channel.id == id) {
+                let changed = !settings.is_empty();
+                settings
+                    .into_iter()
+                    .for_each(|setting| channel.apply(setting));
+                changed
             } else {
-                None
+                // Don't send this week's announcement if it has already gone
+                // out to other channels.
+                let mut channel = ChannelConfig::new(id);
+                channel.last_announced = last_sunday(now).map(|dt| dt.date_naive());
+                settings
+                    .into_iter()
+                    .for_each(|setting| channel.apply(setting));
+                channels.push(channel);
+                true
             }
         }
-        AnnouncerCommand::UnregisterChannel(id) => {
-            if let Ok(registered) = unregister_channel(&id).await {
-                Some(registered)
-            } else {
-                None
+        AnnouncerCommand::UpdateChannel(id, settings) => {
+            match channels.iter_mut().find(|channel| channel.id == id) {
+                Some(channel) => {
+                    let changed = !settings.is_empty();
+                    settings
+                        .into_iter()
+                        .for_each(|setting| channel.apply(setting));
+                    changed
+                }
+                None => false,
             }
         }
+        AnnouncerCommand::UnregisterChannel(id) => {
+            let len = channels.len();
+            channels.retain(|channel| channel.id != id);
+            channels.len() != len
+        }
     }
 }
 
-fn next_sunday() -> Option<chrono::DateTime<chrono::Local>> {
-    const DAY: u64 = 7; // 7 = sunday, 6 = saturday and so on.
+/// Announcement time, 9:00, on the sunday on or before the given date.
+fn sunday_announcement(date: chrono::NaiveDate, tz: &Tz) -> Option<DateTime<Tz>> {
     const HOUR: u32 = 9;
     const MIN: u32 = 0;
 
-    let now = chrono::Local::now();
-    let nd = now.date_naive();
-    nd.checked_sub_days(chrono::Days::new(
-        nd.weekday().num_days_from_sunday().into(),
+    date.checked_sub_days(chrono::Days::new(
+        date.weekday().num_days_from_sunday().into(),
     ))
-    .and_then(|nd| nd.checked_add_days(chrono::Days::new(DAY)))
     .and_then(|nd| chrono::NaiveTime::from_hms_opt(HOUR, MIN, 0).map(|nt| nd.and_time(nt)))
-    .and_then(|ndt| chrono::TimeZone::from_local_datetime(&chrono::Local, &ndt).single())
-    .and_then(|dt| {
-        if dt.signed_duration_since(now) < chrono::Duration::zero() {
-            dt.checked_add_days(chrono::Days::new(7))
-        } else {
-            Some(dt)
-        }
-    })
+    .and_then(|ndt| chrono::TimeZone::from_local_datetime(tz, &ndt).single())
+}
+
+/// The most recent announcement time at or before `now`.
+fn last_sunday(now: &DateTime<Tz>) -> Option<DateTime<Tz>> {
+    let dt = sunday_announcement(now.date_naive(), &now.timezone())?;
+    if dt <= *now {
+        Some(dt)
+    } else {
+        sunday_announcement(dt.date_naive() - chrono::Days::new(7), &now.timezone())
+    }
 }
 
-pub async fn run_announcer(mut commands: UnboundedReceiver<AnnouncerCommand>) {
+/// The first announcement time after `now`.
+fn next_sunday(now: &DateTime<Tz>) -> Option<DateTime<Tz>> {
+    let dt = sunday_announcement(now.date_naive(), &now.timezone())?;
+    if dt > *now {
+        Some(dt)
+    } else {
+        sunday_announcement(dt.date_naive() + chrono::Days::new(7), &now.timezone())
+    }
+}
+
+/// The announcement owed to a channel last announced on `last`, if the most
+/// recent announcement time hasn't been announced and is within `grace`.
+fn missed_announcement(
+    last: Option<chrono::NaiveDate>,
+    now: &DateTime<Tz>,
+    grace: chrono::Duration,
+) -> Option<DateTime<Tz>> {
+    let slot = last_sunday(now)?;
+    if last.is_some_and(|last| last >= slot.date_naive()) || now.signed_duration_since(slot) > grace
+    {
+        None
+    } else {
+        Some(slot)
+    }
+}
+
+/// Wait until 9:00 next sunday morning, or a few minutes if announcements
+/// need to be retried, returning the time waited until.
+async fn wait_for_announcement(clock: &dyn Clock, retry: bool) -> DateTime<Tz> {
+    const RETRY_MINUTES: i64 = 5;
+
+    let now = clock.now();
+    let next = next_sunday(&now).unwrap_or_else(|| now + chrono::Duration::days(7));
+    let until = if retry {
+        next.min(now + chrono::Duration::minutes(RETRY_MINUTES))
+    } else {
+        next
+    };
+
+    println!("Sleeping until: {until} for next announcement.");
+    clock.sleep_until(until).await;
+    until
+}
+
+pub async fn run_announcer(
+    mut commands: UnboundedReceiver<AnnouncerCommand>,
+    clock: Arc<dyn Clock>,
+) {
     let channels = Arc::new(Mutex::new(load_channels().await));
 
     // Handle commands to register and deregister channels for announcements.
     // The other end of this channel is used to pass commands through from
     // discord interactions.
     let command_channels = channels.clone();
+    let command_clock = clock.clone();
     tokio::task::spawn(async move {
         while let Some(command) = commands.recv().await {
-            if let Some(updated) = handle_command(command).await {
-                let mut lock = command_channels.lock().await;
-                *lock = updated;
+            let mut lock = command_channels.lock().await;
+            if handle_command(command, &mut lock, &command_clock.now()) {
+                save_channels(&lock).await;
             }
         }
     });
 
-    // Publish announcements to all registered channels every sunday morning.
+    tokio::task::spawn(run_reminders(channels.clone(), clock.clone()));
+    tokio::task::spawn(changes::run_changes(channels.clone(), clock.clone()));
+    tokio::task::spawn(scheduled::run_scheduled(clock.clone()));
+    tokio::task::spawn(corrections::run_corrections(
+        channels.clone(),
+        clock.clone(),
+    ));
+
+    // Publish announcements to all registered channels every sunday morning,
+    // first catching up on any missed while the bot was down.
     tokio::task::spawn(async move {
         loop {
-            // Wait until 9:00 next sunday morning or 1 week on error.
-            let until_next_sunday = if let Some(dt) = next_sunday() {
-                println!("Sleeping until: {dt} for next announcement.");
-                dt.signed_duration_since(chrono::Local::now()).to_std().ok()
-            } else {
-                None
-            };
-
-            let duration = until_next_sunday
-                .unwrap_or_else(|| std::time::Duration::from_secs(60 * 60 * 24 * 7));
-            let instant = std::time::Instant::now() + duration;
-
-            tokio::time::interval_at(
-                tokio::time::Instant::from_std(instant),
-                std::time::Duration::from_secs(1),
-            )
-            .tick()
-            .await;
-
-            // Run announcement.
-            announce(&channels.lock().await).await;
+            let retry = catch_up(&channels, &clock.now()).await;
+            wait_for_announcement(clock.as_ref(), retry).await;
         }
     });
 }
+
+#[cfg(test)]
+mod test {
+    use chrono::{DateTime, TimeZone};
+    use chrono_tz::{Australia::Sydney, Europe::London, Tz};
+
+    use crate::clock::{Clock, FakeClock};
+
+    use super::{
+        digest, digest_pages, handle_command, last_sunday, missed_announcement, next_sunday,
+        quiet_digest, upcoming, wait_for_announcement, AnnouncerCommand, ChannelConfig, Event,
+    };
+    use crate::{
+        channels::{CategoryStyle, ChannelSetting, QuietWeek, Window},
+        discord, rsvp,
+    };
+
+    fn at(tz: Tz, y: i32, m: u32, d: u32, h: u32, min: u32) -> DateTime<Tz> {
+        tz.with_ymd_and_hms(y, m, d, h, min, 0).single().unwrap()
+    }
+
+    fn digest_names(events: Vec<Event>, now: &DateTime<Tz>) -> Vec<String> {
+        digest(events, now, &ChannelConfig::new("1".to_string()))
+            .iter()
+            .flat_map(|embed| embed.fields())
+            .map(|field| field.name.clone())
+            .collect()
+    }
+
+    #[test]
+    fn test_next_sunday_midweek() {
+        let now = at(chrono_tz::UTC, 2024, 1, 31, 12, 0);
+        assert_eq!(
+            next_sunday(&now),
+            Some(at(chrono_tz::UTC, 2024, 2, 4, 9, 0))
+        );
+    }
+
+    #[test]
+    fn test_next_sunday_boundary() {
+        let tz = chrono_tz::UTC;
+        assert_eq!(
+            next_sunday(&at(tz, 2024, 2, 3, 23, 59)),
+            Some(at(tz, 2024, 2, 4, 9, 0))
+        );
+        assert_eq!(
+            next_sunday(&at(tz, 2024, 2, 4, 8, 59)),
+            Some(at(tz, 2024, 2, 4, 9, 0))
+        );
+        assert_eq!(
+            next_sunday(&at(tz, 2024, 2, 4, 9, 0)),
+            Some(at(tz, 2024, 2, 11, 9, 0))
+        );
+
+        assert_eq!(
+            last_sunday(&at(tz, 2024, 2, 4, 8, 59)),
+            Some(at(tz, 2024, 1, 28, 9, 0))
+        );
+        assert_eq!(
+            last_sunday(&at(tz, 2024, 2, 4, 9, 0)),
+            Some(at(tz, 2024, 2, 4, 9, 0))
+        );
+        assert_eq!(
+            last_sunday(&at(tz, 2024, 2, 10, 23, 59)),
+            Some(at(tz, 2024, 2, 4, 9, 0))
+        );
+    }
+
+    #[test]
+    fn test_next_sunday_year_end() {
+        let tz = chrono_tz::UTC;
+        assert_eq!(
+            next_sunday(&at(tz, 2026, 12, 30, 12, 0)),
+            Some(at(tz, 2027, 1, 3, 9, 0))
+        );
+        assert_eq!(
+            next_sunday(&at(tz, 2023, 12, 31, 10, 0)),
+            Some(at(tz, 2024, 1, 7, 9, 0))
+        );
+    }
+
+    #[test]
+    fn test_next_sunday_dst() {
+        // Sydney daylight saving starts at 02:00 on Sunday 6 Oct 2024.
+        let dt = next_sunday(&at(Sydney, 2024, 10, 5, 12, 0)).unwrap();
+        assert_eq!(dt, at(Sydney, 2024, 10, 6, 9, 0));
+        assert_eq!(
+            dt.naive_utc(),
+            at(chrono_tz::UTC, 2024, 10, 5, 22, 0).naive_utc()
+        );
+
+        // London daylight saving ends at 02:00 on Sunday 27 Oct 2024.
+        let dt = next_sunday(&at(London, 2024, 10, 21, 9, 0)).unwrap();
+        assert_eq!(dt, at(London, 2024, 10, 27, 9, 0));
+        assert_eq!(
+            dt.naive_utc(),
+            at(chrono_tz::UTC, 2024, 10, 27, 9, 0).naive_utc()
+        );
+    }
+
+    #[actix_web::test]
+    async fn test_announcement_fires() {
+        let clock = FakeClock::new(at(Sydney, 2024, 10, 2, 12, 0));
+        let wait = wait_for_announcement(&clock, false);
+        futures::pin_mut!(wait);
+
+        assert!(futures::poll!(&mut wait).is_pending());
+
+        clock.set(at(Sydney, 2024, 10, 6, 8, 59));
+        assert!(futures::poll!(&mut wait).is_pending());
+
+        clock.advance(chrono::Duration::minutes(1));
+        assert_eq!(
+            futures::poll!(&mut wait),
+            std::task::Poll::Ready(at(Sydney, 2024, 10, 6, 9, 0))
+        );
+    }
+
+    #[actix_web::test]
+    async fn test_announcements_weekly() {
+        let clock = FakeClock::new(at(London, 2024, 10, 16, 12, 0));
+        let mut fired = Vec::new();
+        for _ in 0..3 {
+            let wait = wait_for_announcement(&clock, false);
+            futures::pin_mut!(wait);
+            assert!(futures::poll!(&mut wait).is_pending());
+            clock.advance(chrono::Duration::days(7));
+            if let std::task::Poll::Ready(dt) = futures::poll!(&mut wait) {
+                fired.push(dt);
+            }
+            clock.set(fired.last().unwrap().to_owned() + chrono::Duration::hours(1));
+        }
+
+        assert_eq!(
+            fired,
+            vec![
+                at(London, 2024, 10, 20, 9, 0),
+                at(London, 2024, 10, 27, 9, 0),
+                at(London, 2024, 11, 3, 9, 0),
+            ]
+        );
+        assert_eq!(clock.now(), at(London, 2024, 11, 3, 10, 0));
+    }
+
+    #[actix_web::test]
+    async fn test_announcement_retry() {
+        let clock = FakeClock::new(at(London, 2024, 10, 20, 9, 0));
+        let wait = wait_for_announcement(&clock, true);
+        futures::pin_mut!(wait);
+        assert!(futures::poll!(&mut wait).is_pending());
+        clock.advance(chrono::Duration::minutes(5));
+        assert_eq!(
+            futures::poll!(&mut wait),
+            std::task::Poll::Ready(at(London, 2024, 10, 20, 9, 5))
+        );
+    }
+
+    #[test]
+    fn test_missed_announcement() {
+        let tz = chrono_tz::UTC;
+        let grace = chrono::Duration::hours(24);
+        let sunday = at(tz, 2024, 2, 4, 9, 0);
+        let last_week = Some(at(tz, 2024, 1, 28, 9, 0).date_naive());
+
+        // Due exactly on time.
+        assert_eq!(missed_announcement(last_week, &sunday, grace), Some(sunday));
+
+        // Restarted shortly after sending, so already announced.
+        assert_eq!(
+            missed_announcement(
+                Some(sunday.date_naive()),
+                &(sunday + chrono::Duration::seconds(30)),
+                grace
+            ),
+            None
+        );
+
+        // Down over the announcement time but back within the grace window.
+        assert_eq!(
+            missed_announcement(last_week, &at(tz, 2024, 2, 5, 8, 0), grace),
+            Some(sunday)
+        );
+        assert_eq!(
+            missed_announcement(None, &at(tz, 2024, 2, 5, 9, 0), grace),
+            Some(sunday)
+        );
+
+        // Back too late to bother.
+        assert_eq!(
+            missed_announcement(last_week, &at(tz, 2024, 2, 5, 9, 1), grace),
+            None
+        );
+
+        // Not yet due.
+        assert_eq!(
+            missed_announcement(last_week, &at(tz, 2024, 2, 4, 8, 59), grace),
+            None
+        );
+    }
+
+    #[test]
+    fn test_register_skips_sent_week() {
+        let now = at(chrono_tz::UTC, 2024, 2, 4, 10, 0);
+        let mut channels = Vec::new();
+        assert!(handle_command(
+            AnnouncerCommand::RegisterChannel("1".to_string(), Vec::new()),
+            &mut channels,
+            &now
+        ));
+        assert!(!handle_command(
+            AnnouncerCommand::RegisterChannel("1".to_string(), Vec::new()),
+            &mut channels,
+            &now
+        ));
+        assert_eq!(channels.len(), 1);
+        assert_eq!(
+            missed_announcement(
+                channels[0].last_announced,
+                &now,
+                chrono::Duration::hours(24)
+            ),
+            None
+        );
+
+        assert!(handle_command(
+            AnnouncerCommand::UnregisterChannel("1".to_string()),
+            &mut channels,
+            &now
+        ));
+        assert_eq!(channels, Vec::<ChannelConfig>::new());
+    }
+
+    #[test]
+    fn test_update_ignores_unknown_channel() {
+        let now = at(chrono_tz::UTC, 2024, 2, 4, 10, 0);
+        let mut channels = Vec::new();
+        assert!(!handle_command(
+            AnnouncerCommand::UpdateChannel("1".to_string(), vec![ChannelSetting::Teaser(false)]),
+            &mut channels,
+            &now
+        ));
+        assert!(channels.is_empty());
+
+        handle_command(
+            AnnouncerCommand::RegisterChannel("1".to_string(), Vec::new()),
+            &mut channels,
+            &now,
+        );
+        assert!(handle_command(
+            AnnouncerCommand::UpdateChannel("1".to_string(), vec![ChannelSetting::Teaser(false)]),
+            &mut channels,
+            &now
+        ));
+        assert!(!channels[0].teaser);
+    }
+
+    #[test]
+    fn test_digest_week() {
+        let events = vec![
+            Event::new("Past", "03 Feb 2024"),
+            Event::new("Today", "04 Feb 2024"),
+            Event::new("Monday", "05 Feb 2024"),
+            Event::new("Range", "10-11 Feb 2024"),
+            Event::new("Next Sunday", "11 Feb 2024"),
+            Event::new("Too Late", "12 Feb 2024"),
+            Event::new("Unparseable", "Sometime soon"),
+        ];
+        assert_eq!(
+            digest_names(events, &at(chrono_tz::UTC, 2024, 2, 4, 9, 0)),
+            vec!["Monday", "Range", "Next Sunday"]
+        );
+    }
+
+    #[test]
+    fn test_digest_empty() {
+        let events = vec![Event::new("Too Late", "12 Feb 2024")];
+        assert!(digest(
+            events,
+            &at(chrono_tz::UTC, 2024, 2, 4, 9, 0),
+            &ChannelConfig::new("1".to_string())
+        )
+        .is_empty());
+    }
+
+    #[test]
+    fn test_quiet_digest() {
+        let now = at(chrono_tz::UTC, 2024, 2, 4, 9, 0);
+        let events = vec![
+            Event::new("Later", "20 Feb 2024"),
+            Event::new("Sooner", "12 Feb 2024"),
+            Event::new("Past", "01 Feb 2024"),
+        ];
+        let mut channel = ChannelConfig::new("1".to_string());
+        assert!(quiet_digest(&events, &now, &channel).is_none());
+
+        channel.quiet_week = QuietWeek::Message;
+        let embed = quiet_digest(&events, &now, &channel).unwrap();
+        assert_eq!(
+            embed.description(),
+            Some("No events are on from Sunday 04/02 through Sunday 11/02.")
+        );
+        assert!(embed.fields().is_empty());
+
+        channel.quiet_week = QuietWeek::NextEvent;
+        let embed = quiet_digest(&events, &now, &channel).unwrap();
+        assert_eq!(embed.fields()[0].name, "Next up: Sooner");
+    }
+
+    #[test]
+    fn test_digest_window() {
+        let now = at(chrono_tz::UTC, 2024, 2, 4, 9, 0);
+        let events = || {
+            vec![
+                Event::new("Tuesday", "06 Feb 2024"),
+                Event::new("Thursday", "08 Feb 2024"),
+                Event::new("Fortnight", "18 Feb 2024"),
+                Event::new("Leap Day", "29 Feb 2024"),
+                Event::new("March", "01 Mar 2024"),
+            ]
+        };
+
+        let mut channel = ChannelConfig::new("1".to_string());
+        channel.window = Window::Days(3);
+        let embed = &digest(events(), &now, &channel)[0];
+        assert_eq!(embed.title(), Some("Events in the Next 3 Days"));
+        assert_eq!(
+            embed.description(),
+            Some("Sunday 04/02 through Wednesday 07/02")
+        );
+        assert_eq!(embed.fields()[0].name, "Tuesday");
+        assert_eq!(embed.fields().len(), 1);
+
+        channel.window = Window::RestOfMonth;
+        let embed = &digest(events(), &now, &channel)[0];
+        assert_eq!(embed.title(), Some("Events this Month"));
+        let names: Vec<&str> = embed.fields().iter().map(|f| f.name.as_str()).collect();
+        assert_eq!(names, vec!["Tuesday", "Thursday", "Fortnight", "Leap Day"]);
+    }
+
+    #[test]
+    fn test_digest_teaser() {
+        let now = at(chrono_tz::UTC, 2024, 2, 4, 9, 0);
+        let mut events = vec![
+            Event::new("Tuesday", "06 Feb 2024"),
+            Event::new("Fortnight", "18 Feb 2024"),
+            Event::new("March", "01 Mar 2024"),
+        ];
+
+        events[0].link = Some("https://example.com".to_string());
+
+        let mut channel = ChannelConfig::new("1".to_string());
+        channel.teaser = true;
+        let embeds = digest(events, &now, &channel);
+        assert_eq!(embeds[0].footer_text(), Some("Reply /events for more"));
+        let fields = embeds[0].fields();
+        assert_eq!(fields.len(), 2);
+        assert_eq!(fields[0].name, "Tuesday");
+        assert!(fields[0]
+            .value
+            .ends_with("\n[Details](https://example.com)"));
+        assert_eq!(fields[1].name, "Later this Month");
+        assert_eq!(
+            fields[1].value,
+            "Fortnight, <t:1708257600:D> (<t:1708214400:R>)"
+        );
+    }
+
+    #[test]
+    fn test_digest_pages() {
+        let now = at(chrono_tz::UTC, 2024, 2, 4, 9, 0);
+        let events: Vec<Event> = (0..30)
+            .map(|i| Event::new(&format!("Event {i}"), "06 Feb 2024"))
+            .collect();
+        let later = vec![Event::new("Fortnight", "18 Feb 2024")];
+        let channel = ChannelConfig::new("1".to_string());
+
+        // Each page has an event for each row of RSVP buttons.
+        let pages = digest_pages(&events, &later, &now, &channel);
+        assert_eq!(pages.len(), 6);
+        assert_eq!(pages[0].1.len(), 5);
+        assert_eq!(pages[0].0[0].fields().len(), 5);
+        assert_eq!(pages[1].0[0].title(), Some("Events this Week (continued)"));
+        assert_eq!(pages[1].1[0].name, "Event 5");
+        assert_eq!(pages[5].1.len(), 5);
+        assert_eq!(pages[5].0[0].fields().len(), 6);
+        assert!(pages
+            .iter()
+            .all(|(embeds, _)| embeds.len() == 1 && embeds[0].is_valid()));
+
+        // Pages are also split to fit Discord's limit on the total size.
+        let events: Vec<Event> = (0..5)
+            .map(|i| Event {
+                notes: Some("word ".repeat(250)),
+                ..Event::new(&format!("Event {i} {}", "name ".repeat(50)), "06 Feb 2024")
+            })
+            .collect();
+        let pages = digest_pages(&events, &[], &now, &channel);
+        assert_eq!(pages.len(), 2);
+        assert!(pages.iter().all(|(embeds, _)| embeds[0].is_valid()));
+
+        // Leaving room for the RSVP counts added to each field.
+        assert!(pages.iter().all(|(embeds, events)| {
+            embeds[0].length() + events.len() * rsvp::MAX_COUNTS_LENGTH
+                <= discord::Embed::MAX_LENGTH
+        }));
+
+        // Long notes are cut short rather than failing the whole digest.
+        let mut long = Event::new("Long", "06 Feb 2024");
+        long.notes = Some("word ".repeat(1000));
+        let pages = digest_pages(std::slice::from_ref(&long), &[], &now, &channel);
+        assert_eq!(pages.len(), 1);
+        assert!(pages[0].0[0].is_valid());
+        assert!(pages[0].0[0].fields()[0].value.ends_with('…'));
+    }
+
+    #[test]
+    fn test_digest_grouped() {
+        let now = at(chrono_tz::UTC, 2024, 2, 4, 9, 0);
+        let categorised = |name: &str, date: &str, category: Option<&str>| Event {
+            category: category.map(str::to_string),
+            ..Event::new(name, date)
+        };
+        let events = vec![
+            categorised("Write-in", "08 Feb 2024", Some("Write-ins")),
+            categorised("Pub", "07 Feb 2024", Some("Socials")),
+            categorised("Workshop", "06 Feb 2024", Some("Workshops")),
+            categorised("Drinks", "05 Feb 2024", Some("socials")),
+            categorised("Mystery", "09 Feb 2024", None),
+            categorised("Sprint", "10 Feb 2024", Some("write-ins")),
+        ];
+        let mut channel = ChannelConfig::new("1".to_string());
+        assert_eq!(
+            digest_names(events.clone(), &now),
+            vec!["Drinks", "Workshop", "Pub", "Write-in", "Mystery", "Sprint"]
+        );
+
+        channel.filter.exclude = vec!["Socials".to_string()];
+        let (filtered, _) = upcoming(events.clone(), &now, &channel);
+        let names: Vec<_> = filtered.iter().map(|e| e.name.as_str()).collect();
+        assert_eq!(names, vec!["Workshop", "Write-in", "Mystery", "Sprint"]);
+        channel.filter.exclude.clear();
+
+        channel.group_by_category = true;
+        channel.apply(ChannelSetting::Category(CategoryStyle {
+            name: "Socials".to_string(),
+            emoji: Some("🍻".to_string()),
+            colour: Some(0xff8800),
+        }));
+        let (events, later) = upcoming(events, &now, &channel);
+        let pages = digest_pages(&events, &later, &now, &channel);
+
+        // Categories share a message until it has a field for each row of
+        // RSVP buttons, and those differing only in case are listed together.
+        let headings: Vec<Vec<_>> = pages
+            .iter()
+            .map(|(embeds, _)| embeds.iter().map(|embed| embed.title()).collect())
+            .collect();
+        assert_eq!(
+            headings,
+            vec![
+                vec![
+                    Some("Events this Week: 🍻 Socials"),
+                    Some("Workshops"),
+                    Some("Write-ins"),
+                ],
+                vec![Some("Events this Week: Other")],
+            ]
+        );
+        let names: Vec<_> = pages[0].1.iter().map(|e| e.name.as_str()).collect();
+        assert_eq!(
+            names,
+            vec!["Drinks", "Pub", "Workshop", "Write-in", "Sprint"]
+        );
+        assert_eq!(pages[0].0[2].fields().len(), 2);
+        assert_eq!(
+            serde_json::to_value(&pages[0].0[0]).unwrap()["color"],
+            0xff8800
+        );
+    }
+
+    #[test]
+    fn test_digest_year_end() {
+        let events = vec![
+            Event::new("Christmas", "25 Dec 2026"),
+            Event::new("New Year", "01 Jan 2027"),
+            Event::new("Sunday", "03 Jan 2027"),
+            Event::new("Monday", "04 Jan 2027"),
+        ];
+        assert_eq!(
+            digest_names(events, &at(chrono_tz::UTC, 2026, 12, 27, 9, 0)),
+            vec!["New Year", "Sunday"]
+        );
+    }
+
+    #[test]
+    fn test_digest_dst() {
+        // Sydney daylight saving ends at 03:00 on Sunday 7 Apr 2024, making
+        // the week an hour longer.
+        let events = vec![
+            Event::new("Saturday", "06 Apr 2024"),
+            Event::new("Sunday", "07 Apr 2024"),
+            Event::new("Monday", "08 Apr 2024"),
+        ];
+        assert_eq!(
+            digest_names(events, &at(Sydney, 2024, 3, 31, 9, 0)),
+            vec!["Saturday", "Sunday"]
+        );
+
+        // Daylight saving starts at 02:00 on Sunday 6 Oct 2024.
+        let events = vec![
+            Event::new("Saturday", "12 Oct 2024"),
+            Event::new("Sunday", "13 Oct 2024"),
+            Event::new("Monday", "14 Oct 2024"),
+        ];
+        assert_eq!(
+            digest_names(events, &at(Sydney, 2024, 10, 6, 9, 0)),
+            vec!["Saturday", "Sunday"]
+        );
+    }
+}
@@ -0,0 +1,143 @@
+use crate::{
+    discord::{self, ApplicationCommandOptionChoice},
+    events::{load_announcements, Event},
+};
+
+/// Most choices Discord will show.
+const MAX_CHOICES: usize = 25;
+
+/// Discord's limit on the length of a choice's name and value.
+const MAX_CHOICE_LENGTH: usize = 100;
+
+/// Discord drops autocomplete responses after three seconds, so give up on
+/// loading events well before then.
+const LOAD_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(2);
+
+/// How closely a candidate matches what the user has typed, best first, or
+/// None if it doesn't match at all.
+fn rank(candidate: &str, query: &str) -> Option<u8> {
+    let candidate = candidate.to_lowercase();
+    let query = query.trim().to_lowercase();
+
+    if candidate.starts_with(&query) {
+        Some(0)
+    } else if candidate
+        .split_whitespace()
+        .any(|word| word.starts_with(&query))
+    {
+        Some(1)
+    } else {
+        // Allow skipping letters, e.g. "wkshp" for "Workshop".
+        let mut chars = candidate.chars();
+        query
+            .chars()
+            .filter(|c| !c.is_whitespace())
+            .all(|q| chars.any(|c| c == q))
+            .then_some(2)
+    }
+}
+
+/// Candidates matching the query, best matches first, without duplicates.
+fn suggestions<'a, I>(candidates: I, query: &str) -> Vec<&'a str>
+where
+    I: IntoIterator<Item = &'a str>,
+{
+    let mut matches: Vec<(u8, &str)> = Vec::new();
+    for candidate in candidates.into_iter().map(str::trim) {
+        if candidate.is_empty()
+            || candidate.chars().count() > MAX_CHOICE_LENGTH
+            || matches
+                .iter()
+                .any(|(_, m)| m.eq_ignore_ascii_case(candidate))
+        {
+            continue;
+        }
+
+        if let Some(rank) = rank(candidate, query) {
+            matches.push((rank, candidate));
+        }
+    }
+
+    matches.sort_by_key(|(rank, candidate)| (*rank, candidate.to_lowercase()));
+    matches
+        .into_iter()
+        .take(MAX_CHOICES)
+        .map(|(_, candidate)| candidate)
+        .collect()
+}
+
+/// Values of an event which can be suggested for an option.
+fn field<'a>(command: &str, option: &str, event: &'a Event) -> Option<&'a str> {
+    match (command, option) {
+        ("events" | "category", "category") => event.category.as_deref(),
+        ("events", "location") => Some(&event.location),
+        ("attendees", "event") => Some(&event.name),
+        _ => None,
+    }
+}
+
+/// Suggest values for the option being typed, drawn from the loaded events.
+pub async fn respond(interaction: &discord::Interaction) -> discord::InteractionResponse {
+    let (Some(command), Some((option, value))) =
+        (interaction.command(), interaction.focused_option())
+    else {
+        return discord::InteractionResponse::autocomplete(Vec::new());
+    };
+    let query = value.as_str().unwrap_or_default();
+
+    let events = match tokio::time::timeout(LOAD_TIMEOUT, load_announcements()).await {
+        Ok(Ok(events)) => events,
+        Ok(Err(e)) => {
+            eprintln!("Failed to load events: {e}");
+            Vec::new()
+        }
+        Err(_) => Vec::new(),
+    };
+
+    let candidates = events
+        .iter()
+        .filter_map(|event| field(command, option, event));
+    discord::InteractionResponse::autocomplete(
+        suggestions(candidates, query)
+            .into_iter()
+            .map(|s| ApplicationCommandOptionChoice::new(s, s))
+            .collect(),
+    )
+}
+
+#[cfg(test)]
+mod test {
+    use super::{rank, suggestions, MAX_CHOICES};
+
+    #[test]
+    fn test_rank() {
+        assert_eq!(rank("Workshop", "work"), Some(0));
+        assert_eq!(rank("Poetry Workshop", "WORK"), Some(1));
+        assert_eq!(rank("Workshop", "wkshp"), Some(2));
+        assert_eq!(rank("Workshop", "pub"), None);
+        assert_eq!(rank("Anything", ""), Some(0));
+    }
+
+    #[test]
+    fn test_suggestions() {
+        let candidates = [
+            "Poetry Workshop",
+            "Workshop",
+            "workshop",
+            "The Pub",
+            "Writers' Walk",
+            "",
+        ];
+        assert_eq!(
+            suggestions(candidates, "w"),
+            vec!["Workshop", "Writers' Walk", "Poetry Workshop"]
+        );
+        assert_eq!(suggestions(candidates, "pub"), vec!["The Pub"]);
+
+        let many: Vec<String> = (0..30).map(|i| format!("Event {i}")).collect();
+        assert_eq!(
+            suggestions(many.iter().map(String::as_str), "ev").len(),
+            MAX_CHOICES
+        );
+    }
+}
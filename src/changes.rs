@@ -0,0 +1,336 @@
+use std::{collections::HashMap, sync::Arc};
+
+use chrono::DateTime;
+use chrono_tz::Tz;
+use tokio::sync::Mutex;
+
+use crate::{
+    announcer::{send_message, CreateMessageRequest},
+    channels::ChannelConfig,
+    clock::Clock,
+    csv::{load_csv, write_csv, Csv},
+    discord,
+    events::{load_announcements, Event},
+};
+
+/// Events as of the last check, in the events sheet layout.
+const SNAPSHOT_CSV: &str = "snapshot.csv";
+
+/// Events as of the last notice each channel whose notices failed was sent,
+/// as rows of the channel's id followed by the event's row, so that only
+/// those channels are sent the changes again.
+const PENDING_CSV: &str = "snapshot_pending.csv";
+
+/// How often to check the events for changes.
+const POLL_MINUTES: i64 = 15;
+
+/// A change to an upcoming event since the last check.
+#[derive(Debug, PartialEq)]
+enum Change {
+    New(Event),
+    Moved { event: Event, from: Event },
+    Cancelled(Event),
+}
+
+impl Change {
+    /// The event as it is now, or was before it was cancelled.
+    fn event(&self) -> &Event {
+        match self {
+            Self::New(event) | Self::Moved { event, .. } | Self::Cancelled(event) => event,
+        }
+    }
+
+    /// Field describing the change in a notice.
+    fn field(&self, tz: &Tz) -> (String, String) {
+        match self {
+            Self::New(event) => (format!("New: {}", event.name), event.summary(tz)),
+            Self::Moved { event, from } => (
+                format!("Moved: {}", event.name),
+                format!(
+                    "Moved from {} to {}",
+                    from.date_string(tz),
+                    event.date_string(tz)
+                ),
+            ),
+            Self::Cancelled(event) => (format!("Cancelled: {}", event.name), event.summary(tz)),
+        }
+    }
+}
+
+/// Changes between two snapshots of the events. Events are identified by name
+/// and date, so an upcoming event which disappears while another of the same
+/// name appears has moved, to the closest date if there are several. Changes
+/// to past events aren't worth a notice, and past events which disappear,
+/// such as last week's instance of a recurring event, haven't moved.
+fn diff(old: &[Event], new: &[Event], now: &DateTime<Tz>) -> Vec<Change> {
+    let tz = now.timezone();
+    let upcoming = |event: &Event| event.start_time(&tz).is_some_and(|start| start >= *now);
+    let distance = |a: &Event, b: &Event| {
+        a.start_time(&tz)
+            .zip(b.start_time(&tz))
+            .map_or(i64::MAX, |(a, b)| (a - b).num_seconds().abs())
+    };
+
+    let mut removed: Vec<Option<&Event>> = old
+        .iter()
+        .filter(|o| upcoming(o) && !new.iter().any(|n| n.key() == o.key()))
+        .map(Some)
+        .collect();
+
+    let mut changes = Vec::new();
+    for event in new
+        .iter()
+        .filter(|n| !old.iter().any(|o| o.key() == n.key()))
+    {
+        let from = removed
+            .iter_mut()
+            .filter(|o| o.is_some_and(|o| o.has_name(&event.name)))
+            .min_by_key(|o| o.map_or(i64::MAX, |o| distance(o, event)))
+            .and_then(Option::take);
+        match from {
+            Some(from) => changes.push(Change::Moved {
+                event: event.clone(),
+                from: from.clone(),
+            }),
+            None if upcoming(event) => changes.push(Change::New(event.clone())),
+            None => {}
+        }
+    }
+
+    changes.extend(
+        removed
+            .into_iter()
+            .flatten()
+            .map(|event| Change::Cancelled(event.clone())),
+    );
+    changes
+}
+
+/// Embeds listing the changes, split to fit Discord's limits.
+fn notices(changes: &[&Change], tz: &Tz) -> Vec<discord::Embed> {
+    const TITLE: &str = "Event Updates";
+
+    let mut embeds = Vec::new();
+    let mut embed = discord::Embed::new(TITLE, "");
+    for change in changes {
+        let (name, value) = change.field(tz);
+        if !embed.has_room(&name, &value) {
+            embeds.push(embed);
+            embed = discord::Embed::new(TITLE, "");
+        }
+        embed.add_field(name, value);
+    }
+    if !embed.fields().is_empty() {
+        embeds.push(embed);
+    }
+    embeds
+}
+
+/// Events each channel was last notified of, from the rows of the pending
+/// file. Each channel has a row of just its id, so that one notified of no
+/// events is kept.
+fn pending_from_rows(rows: &Csv) -> HashMap<discord::Snowflake, Vec<Event>> {
+    let mut pending: HashMap<discord::Snowflake, Vec<Event>> = HashMap::new();
+    for row in rows {
+        if let Some((channel, event)) = row.split_first() {
+            let events = pending.entry(channel.clone()).or_default();
+            events.extend(Event::from_row(event));
+        }
+    }
+    pending
+}
+
+fn pending_to_rows(pending: &[(&discord::Snowflake, &[Event])]) -> Csv {
+    let mut rows = Vec::new();
+    for (channel, events) in pending {
+        rows.push(vec![channel.to_string()]);
+        rows.extend(events.iter().map(|event| {
+            let mut row = vec![channel.to_string()];
+            row.extend(event.to_row());
+            row
+        }));
+    }
+    rows
+}
+
+/// Post notices of the changes relevant to a channel, returning whether they
+/// were all sent.
+async fn notify(channel: &ChannelConfig, changes: &[Change], tz: &Tz) -> bool {
+    let relevant: Vec<&Change> = changes
+        .iter()
+        .filter(|change| channel.filter.matches(change.event()))
+        .collect();
+    for embed in notices(&relevant, tz) {
+        let body = CreateMessageRequest {
+            embeds: Some(vec![embed]),
+            allowed_mentions: Some(discord::AllowedMentions::none()),
+            ..Default::default()
+        };
+        if let Err(e) = send_message(body, &channel.id).await {
+            eprintln!("Failed to post changes: {e}");
+            return false;
+        }
+    }
+    true
+}
+
+/// Compare the events with the last snapshot, posting notices of any changes
+/// to subscribed channels. Channels whose notices fail are compared with the
+/// events they were last notified of instead until they succeed.
+async fn check(channels: &[ChannelConfig], now: &DateTime<Tz>) {
+    let events = match load_announcements().await {
+        Ok(events) => events,
+        Err(e) => {
+            eprintln!("Failed to load events: {e}");
+            return;
+        }
+    };
+
+    // Without a snapshot, there's nothing to compare against yet.
+    if let Ok(snapshot) = load_csv(SNAPSHOT_CSV).await {
+        let old: Vec<Event> = snapshot
+            .iter()
+            .filter_map(|row| Event::from_row(row))
+            .collect();
+        let pending = pending_from_rows(&load_csv(PENDING_CSV).await.unwrap_or_default());
+
+        let mut failed = Vec::new();
+        for channel in channels.iter().filter(|c| c.notify_changes) {
+            let since = pending.get(&channel.id).unwrap_or(&old);
+            let changes = diff(since, &events, now);
+            if !notify(channel, &changes, &now.timezone()).await {
+                failed.push((&channel.id, since.as_slice()));
+            }
+        }
+        if let Err(e) = write_csv(&pending_to_rows(&failed), PENDING_CSV).await {
+            eprintln!("{e}");
+        }
+    }
+
+    if let Err(e) = write_csv(&events.iter().map(Event::to_row).collect(), SNAPSHOT_CSV).await {
+        eprintln!("{e}");
+    }
+}
+
+/// Periodically post notices of changes to the events.
+pub async fn run_changes(channels: Arc<Mutex<Vec<ChannelConfig>>>, clock: Arc<dyn Clock>) {
+    loop {
+        let registered = channels.lock().await.clone();
+        let now = clock.now();
+        check(&registered, &now).await;
+        clock
+            .sleep_until(now + chrono::Duration::minutes(POLL_MINUTES))
+            .await;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use chrono::TimeZone;
+
+    use crate::events::Event;
+
+    use super::{diff, notices, pending_from_rows, pending_to_rows, Change};
+
+    #[test]
+    fn test_diff() {
+        let now = chrono_tz::UTC
+            .with_ymd_and_hms(2024, 2, 4, 9, 0, 0)
+            .unwrap();
+        let old = vec![
+            Event::new("Workshop", "06 Feb 2024"),
+            Event::new("Social", "07 Feb 2024"),
+            Event::new("Launch", "08 Feb 2024"),
+            Event::new("Last Week", "01 Feb 2024"),
+        ];
+        let new = vec![
+            Event::new("Workshop", "06 Feb 2024"),
+            Event::new("Social", "09 Feb 2024"),
+            Event::new("Write-in", "10 Feb 2024"),
+        ];
+
+        assert_eq!(
+            diff(&old, &new, &now),
+            vec![
+                Change::Moved {
+                    event: Event::new("Social", "09 Feb 2024"),
+                    from: Event::new("Social", "07 Feb 2024"),
+                },
+                Change::New(Event::new("Write-in", "10 Feb 2024")),
+                Change::Cancelled(Event::new("Launch", "08 Feb 2024")),
+            ]
+        );
+        assert!(diff(&new, &new, &now).is_empty());
+    }
+
+    #[test]
+    fn test_diff_recurring() {
+        let now = chrono_tz::UTC
+            .with_ymd_and_hms(2024, 2, 4, 9, 0, 0)
+            .unwrap();
+
+        // Last week's instance dropping off as next week's is added isn't a
+        // move.
+        let old = vec![
+            Event::new("Write-in", "01 Feb 2024"),
+            Event::new("Write-in", "08 Feb 2024"),
+        ];
+        let new = vec![
+            Event::new("Write-in", "08 Feb 2024"),
+            Event::new("Write-in", "15 Feb 2024"),
+        ];
+        assert_eq!(
+            diff(&old, &new, &now),
+            vec![Change::New(Event::new("Write-in", "15 Feb 2024"))]
+        );
+
+        // A moved instance is paired with the closest date.
+        let old = vec![
+            Event::new("Write-in", "08 Feb 2024"),
+            Event::new("Write-in", "15 Feb 2024"),
+        ];
+        let new = vec![Event::new("Write-in", "16 Feb 2024")];
+        assert_eq!(
+            diff(&old, &new, &now),
+            vec![
+                Change::Moved {
+                    event: Event::new("Write-in", "16 Feb 2024"),
+                    from: Event::new("Write-in", "15 Feb 2024"),
+                },
+                Change::Cancelled(Event::new("Write-in", "08 Feb 2024")),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_notices() {
+        let moved = Change::Moved {
+            event: Event::new("Social", "09 Feb 2024"),
+            from: Event::new("Social", "07 Feb 2024"),
+        };
+        let embeds = notices(&[&moved], &chrono_tz::UTC);
+        assert_eq!(embeds.len(), 1);
+        assert_eq!(embeds[0].fields()[0].name, "Moved: Social");
+        assert_eq!(
+            embeds[0].fields()[0].value,
+            "Moved from <t:1707307200:D> (<t:1707264000:R>) to <t:1707480000:D> (<t:1707436800:R>)"
+        );
+        assert!(notices(&[], &chrono_tz::UTC).is_empty());
+    }
+
+    #[test]
+    fn test_pending_rows() {
+        let events = vec![
+            Event::new("Workshop", "06 Feb 2024"),
+            Event::new("Social", "07 Feb 2024"),
+        ];
+        let (first, second) = ("1".to_string(), "2".to_string());
+        let rows = pending_to_rows(&[(&first, &events), (&second, &[])]);
+        assert_eq!(rows[1][..2], ["1", "Workshop"]);
+
+        let pending = pending_from_rows(&rows);
+        assert_eq!(pending.len(), 2);
+        assert_eq!(pending[&first], events);
+        assert!(pending[&second].is_empty());
+    }
+}
@@ -0,0 +1,808 @@
+use chrono::{Datelike, NaiveDate};
+
+use crate::{
+    csv::{load_csv, write_csv},
+    discord, err,
+    events::Event,
+    template::Template,
+    Result,
+};
+
+const CHANNELS_CSV: &str = "channels.csv";
+const CATEGORIES_CSV: &str = "categories.csv";
+const DATE_FORMAT: &str = "%Y-%m-%d";
+
+/// How far ahead of the announcement events are included.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Window {
+    Days(u32),
+    RestOfMonth,
+}
+
+impl Window {
+    const MONTH: &'static str = "month";
+
+    /// The last day of the window beginning on `today`.
+    pub fn last_day(&self, today: NaiveDate) -> Option<NaiveDate> {
+        match self {
+            Self::Days(n) => today.checked_add_days(chrono::Days::new((*n).into())),
+            Self::RestOfMonth => today
+                .with_day(1)
+                .and_then(|first| first.checked_add_months(chrono::Months::new(1)))
+                .and_then(|next| next.pred_opt()),
+        }
+    }
+
+    pub fn title(&self) -> String {
+        match self {
+            Self::Days(7) => "Events this Week".to_string(),
+            Self::Days(n) => format!("Events in the Next {n} Days"),
+            Self::RestOfMonth => "Events this Month".to_string(),
+        }
+    }
+
+    fn parse(text: &str) -> Option<Self> {
+        if text == Self::MONTH {
+            Some(Self::RestOfMonth)
+        } else {
+            text.parse().ok().filter(|n| *n > 0).map(Self::Days)
+        }
+    }
+
+    fn format(&self) -> String {
+        match self {
+            Self::Days(n) => n.to_string(),
+            Self::RestOfMonth => Self::MONTH.to_string(),
+        }
+    }
+}
+
+impl Default for Window {
+    fn default() -> Self {
+        Self::Days(7)
+    }
+}
+
+/// Who to notify when a digest is posted.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub enum Ping {
+    Nobody,
+    Role(discord::Snowflake),
+    Here,
+    #[default]
+    Everyone,
+}
+
+impl Ping {
+    const NOBODY: &'static str = "none";
+    const HERE: &'static str = "here";
+    const EVERYONE: &'static str = "everyone";
+    const ROLE_PREFIX: &'static str = "role:";
+
+    /// Message content carrying the mention, if any.
+    pub fn content(&self) -> Option<String> {
+        match self {
+            Self::Nobody => None,
+            Self::Role(role) => Some(format!("<@&{role}>")),
+            Self::Here => Some("@here".to_string()),
+            Self::Everyone => Some("@everyone".to_string()),
+        }
+    }
+
+    /// Mentions allowed to ping, so that only the configured one fires.
+    pub fn allowed_mentions(&self) -> discord::AllowedMentions {
+        match self {
+            Self::Nobody => discord::AllowedMentions::none(),
+            Self::Role(role) => discord::AllowedMentions::role(role.clone()),
+            Self::Here | Self::Everyone => discord::AllowedMentions::everyone(),
+        }
+    }
+
+    fn parse(text: &str) -> Option<Self> {
+        match text {
+            Self::NOBODY => Some(Self::Nobody),
+            Self::HERE => Some(Self::Here),
+            Self::EVERYONE => Some(Self::Everyone),
+            _ => text
+                .strip_prefix(Self::ROLE_PREFIX)
+                .filter(|role| !role.is_empty())
+                .map(|role| Self::Role(role.to_string())),
+        }
+    }
+
+    fn format(&self) -> String {
+        match self {
+            Self::Nobody => Self::NOBODY.to_string(),
+            Self::Role(role) => format!("{}{role}", Self::ROLE_PREFIX),
+            Self::Here => Self::HERE.to_string(),
+            Self::Everyone => Self::EVERYONE.to_string(),
+        }
+    }
+}
+
+/// What to post when there are no events in the window.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum QuietWeek {
+    #[default]
+    Silent,
+    Message,
+
+    /// The message followed by the next event after the window.
+    NextEvent,
+}
+
+impl QuietWeek {
+    const SILENT: &'static str = "silent";
+    const MESSAGE: &'static str = "message";
+    const NEXT_EVENT: &'static str = "next";
+
+    fn parse(text: &str) -> Option<Self> {
+        match text {
+            Self::SILENT => Some(Self::Silent),
+            Self::MESSAGE => Some(Self::Message),
+            Self::NEXT_EVENT => Some(Self::NextEvent),
+            _ => None,
+        }
+    }
+
+    fn format(&self) -> String {
+        match self {
+            Self::Silent => Self::SILENT,
+            Self::Message => Self::MESSAGE,
+            Self::NextEvent => Self::NEXT_EVENT,
+        }
+        .to_string()
+    }
+}
+
+/// Categories of events announced in a channel. An empty include list
+/// allows every category.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct CategoryFilter {
+    pub include: Vec<String>,
+    pub exclude: Vec<String>,
+}
+
+impl CategoryFilter {
+    const SEPARATOR: char = ',';
+
+    /// Clears the include list.
+    const ALL: &'static str = "all";
+
+    /// Clears the exclude list.
+    const NONE: &'static str = "none";
+
+    pub fn matches(&self, event: &Event) -> bool {
+        let category = event
+            .category
+            .as_deref()
+            .map(|category| category.trim().to_lowercase())
+            .unwrap_or_default();
+        let listed = |list: &[String]| list.iter().any(|c| c.to_lowercase() == category);
+
+        (self.include.is_empty() || listed(&self.include)) && !listed(&self.exclude)
+    }
+
+    fn parse(text: &str) -> Vec<String> {
+        text.split(Self::SEPARATOR)
+            .map(str::trim)
+            .filter(|category| !category.is_empty())
+            .map(str::to_string)
+            .collect()
+    }
+
+    fn format(list: &[String]) -> String {
+        list.join(&Self::SEPARATOR.to_string())
+    }
+}
+
+/// Heading for a category's events when a channel groups its digest, stored
+/// as a row of the categories CSV.
+#[derive(Clone, Debug, PartialEq)]
+pub struct CategoryStyle {
+    pub name: String,
+    pub emoji: Option<String>,
+    pub colour: Option<i32>,
+}
+
+impl CategoryStyle {
+    /// Heading of events without a category.
+    const OTHER: &'static str = "Other";
+
+    pub fn new(name: &str) -> Self {
+        Self {
+            name: name.trim().to_string(),
+            emoji: None,
+            colour: None,
+        }
+    }
+
+    pub fn matches(&self, category: &str) -> bool {
+        self.name.to_lowercase() == category.trim().to_lowercase()
+    }
+
+    pub fn heading(&self) -> String {
+        match &self.emoji {
+            Some(emoji) => format!("{emoji} {}", self.name),
+            None => self.name.clone(),
+        }
+    }
+
+    /// Colour from hex, e.g. `#ff8800`.
+    pub fn parse_colour(text: &str) -> Option<i32> {
+        let hex = text.trim().trim_start_matches('#');
+        if hex.len() == 6 {
+            i32::from_str_radix(hex, 16).ok()
+        } else {
+            None
+        }
+    }
+
+    fn format_colour(colour: i32) -> String {
+        format!("#{colour:06x}")
+    }
+
+    fn from_row(row: Vec<String>) -> Option<(discord::Snowflake, Self)> {
+        let mut cols = row.into_iter();
+        let channel = cols.next().filter(|id| !id.is_empty())?;
+        let name = cols.next().filter(|name| !name.is_empty())?;
+        let emoji = cols.next().filter(|emoji| !emoji.is_empty());
+        let colour = cols.next().and_then(|colour| Self::parse_colour(&colour));
+        Some((
+            channel,
+            Self {
+                name,
+                emoji,
+                colour,
+            },
+        ))
+    }
+
+    fn to_row(&self, channel: &discord::Snowflake) -> Vec<String> {
+        vec![
+            channel.clone(),
+            self.name.clone(),
+            self.emoji.clone().unwrap_or_default(),
+            self.colour.map(Self::format_colour).unwrap_or_default(),
+        ]
+    }
+}
+
+/// A change to a channel's configuration made with the announce command.
+#[derive(Debug, PartialEq)]
+pub enum ChannelSetting {
+    Window(Window),
+    Teaser(bool),
+    Reminder(Option<u32>),
+    Ping(Ping),
+    Template(Template),
+    QuietWeek(QuietWeek),
+    GroupByCategory(bool),
+
+    /// Set a category's style, or clear it if it has neither emoji nor colour.
+    Category(CategoryStyle),
+
+    /// Only announce these categories, or all if empty.
+    Include(Vec<String>),
+
+    /// Never announce these categories.
+    Exclude(Vec<String>),
+
+    NotifyChanges(bool),
+}
+
+impl ChannelSetting {
+    const MAX_REMINDER_HOURS: u32 = 24 * 7;
+
+    /// Settings from the options of an announce command interaction.
+    pub fn from_interaction(interaction: &discord::Interaction) -> Result<Vec<Self>> {
+        let mut settings = Vec::new();
+
+        if let Some(window) = interaction.option_str("window") {
+            match Window::parse(window) {
+                Some(window) => settings.push(Self::Window(window)),
+                None => return err(format!("Invalid window: {window}")),
+            }
+        }
+
+        if let Some(teaser) = interaction.option_bool("teaser") {
+            settings.push(Self::Teaser(teaser));
+        }
+
+        if let Some(hours) = interaction.option_int("reminder") {
+            match u32::try_from(hours) {
+                Ok(0) => settings.push(Self::Reminder(None)),
+                Ok(hours) => settings.push(Self::Reminder(Some(hours))),
+                Err(_) => return err(format!("Invalid reminder: {hours} hours")),
+            }
+        }
+
+        match (
+            interaction.option_str("ping"),
+            interaction.option_str("ping_role"),
+        ) {
+            (Some(_), Some(_)) => return err("Choose either ping or ping_role, not both."),
+            (Some(ping), None) => match Ping::parse(ping) {
+                Some(ping) => settings.push(Self::Ping(ping)),
+                None => return err(format!("Invalid ping: {ping}")),
+            },
+            (None, Some(role)) => settings.push(Self::Ping(Ping::Role(role.to_string()))),
+            (None, None) => {}
+        }
+
+        if let Some(group) = interaction.option_bool("group_by_category") {
+            settings.push(Self::GroupByCategory(group));
+        }
+
+        if let Some(include) = interaction.option_str("include_categories") {
+            if include.trim().eq_ignore_ascii_case(CategoryFilter::ALL) {
+                settings.push(Self::Include(Vec::new()));
+            } else {
+                settings.push(Self::Include(CategoryFilter::parse(include)));
+            }
+        }
+
+        if let Some(exclude) = interaction.option_str("exclude_categories") {
+            if exclude.trim().eq_ignore_ascii_case(CategoryFilter::NONE) {
+                settings.push(Self::Exclude(Vec::new()));
+            } else {
+                settings.push(Self::Exclude(CategoryFilter::parse(exclude)));
+            }
+        }
+
+        if let Some(notify) = interaction.option_bool("notify_changes") {
+            settings.push(Self::NotifyChanges(notify));
+        }
+
+        if let Some(quiet) = interaction.option_str("quiet_week") {
+            match QuietWeek::parse(quiet) {
+                Some(quiet) => settings.push(Self::QuietWeek(quiet)),
+                None => return err(format!("Invalid quiet week option: {quiet}")),
+            }
+        }
+
+        Ok(settings)
+    }
+
+    /// Options accepted by the announce command.
+    pub fn options() -> Vec<discord::ApplicationCommandOption> {
+        use discord::{ApplicationCommandOption, ApplicationCommandOptionType};
+
+        vec![
+            ApplicationCommandOption::new(
+                ApplicationCommandOptionType::String,
+                "window",
+                "How far ahead to announce events.",
+            )
+            .choice("Next 3 days", "3")
+            .choice("Next 7 days", "7")
+            .choice("Next 14 days", "14")
+            .choice("Rest of the month", Window::MONTH),
+            ApplicationCommandOption::new(
+                ApplicationCommandOptionType::Boolean,
+                "teaser",
+                "Also list events later this month.",
+            ),
+            ApplicationCommandOption::new(
+                ApplicationCommandOptionType::Integer,
+                "reminder",
+                "Hours before each event to post a reminder, 0 to disable.",
+            )
+            .min_value(0.0)
+            .max_value(Self::MAX_REMINDER_HOURS.into()),
+            ApplicationCommandOption::new(
+                ApplicationCommandOptionType::String,
+                "ping",
+                "Who to notify when events are announced.",
+            )
+            .choice("Nobody", Ping::NOBODY)
+            .choice("@here", Ping::HERE)
+            .choice("@everyone", Ping::EVERYONE),
+            ApplicationCommandOption::new(
+                ApplicationCommandOptionType::Role,
+                "ping_role",
+                "Notify this role when events are announced.",
+            ),
+            ApplicationCommandOption::new(
+                ApplicationCommandOptionType::String,
+                "quiet_week",
+                "What to post when there are no events.",
+            )
+            .choice("Nothing", QuietWeek::SILENT)
+            .choice("A message", QuietWeek::MESSAGE)
+            .choice("A message and the next event", QuietWeek::NEXT_EVENT),
+            ApplicationCommandOption::new(
+                ApplicationCommandOptionType::Boolean,
+                "group_by_category",
+                "List events under a heading for each category.",
+            ),
+            ApplicationCommandOption::new(
+                ApplicationCommandOptionType::String,
+                "include_categories",
+                "Only announce these categories, separated by commas, or \"all\".",
+            ),
+            ApplicationCommandOption::new(
+                ApplicationCommandOptionType::String,
+                "exclude_categories",
+                "Never announce these categories, separated by commas, or \"none\".",
+            ),
+            ApplicationCommandOption::new(
+                ApplicationCommandOptionType::Boolean,
+                "notify_changes",
+                "Post when events are added, moved or cancelled.",
+            ),
+        ]
+    }
+}
+
+/// A channel registered for announcements, stored as a row of the channels
+/// CSV. Columns after the id are optional so that older files still load.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ChannelConfig {
+    pub id: discord::Snowflake,
+
+    /// Date of the last Sunday for which an announcement was sent.
+    pub last_announced: Option<NaiveDate>,
+
+    pub window: Window,
+
+    /// Whether to list events after the window but within the month.
+    pub teaser: bool,
+
+    /// Hours before each event to post a reminder, if enabled.
+    pub reminder_hours: Option<u32>,
+
+    pub ping: Ping,
+
+    pub template: Template,
+
+    pub quiet_week: QuietWeek,
+
+    pub group_by_category: bool,
+
+    /// Styles of category headings, stored in the categories CSV.
+    pub categories: Vec<CategoryStyle>,
+
+    pub filter: CategoryFilter,
+
+    /// Whether to post notices when events are added, moved or cancelled.
+    pub notify_changes: bool,
+}
+
+impl ChannelConfig {
+    pub fn new(id: discord::Snowflake) -> Self {
+        Self {
+            id,
+            last_announced: None,
+            window: Window::default(),
+            teaser: false,
+            reminder_hours: None,
+            ping: Ping::default(),
+            template: Template::default(),
+            quiet_week: QuietWeek::default(),
+            group_by_category: false,
+            categories: Vec::new(),
+            filter: CategoryFilter::default(),
+            notify_changes: false,
+        }
+    }
+
+    pub fn apply(&mut self, setting: ChannelSetting) {
+        match setting {
+            ChannelSetting::Window(window) => self.window = window,
+            ChannelSetting::Teaser(teaser) => self.teaser = teaser,
+            ChannelSetting::Reminder(hours) => self.reminder_hours = hours,
+            ChannelSetting::Ping(ping) => self.ping = ping,
+            ChannelSetting::Template(template) => self.template = template,
+            ChannelSetting::QuietWeek(quiet) => self.quiet_week = quiet,
+            ChannelSetting::GroupByCategory(group) => self.group_by_category = group,
+            ChannelSetting::Include(include) => self.filter.include = include,
+            ChannelSetting::Exclude(exclude) => self.filter.exclude = exclude,
+            ChannelSetting::NotifyChanges(notify) => self.notify_changes = notify,
+            ChannelSetting::Category(style) => {
+                self.categories.retain(|s| !s.matches(&style.name));
+                if style.emoji.is_some() || style.colour.is_some() {
+                    self.categories.push(style);
+                }
+            }
+        }
+    }
+
+    fn from_row(row: Vec<String>) -> Option<Self> {
+        let mut cols = row.into_iter();
+        let id = cols.next().filter(|id| !id.is_empty())?;
+        let last_announced = cols
+            .next()
+            .and_then(|date| NaiveDate::parse_from_str(&date, DATE_FORMAT).ok());
+        let window = cols
+            .next()
+            .and_then(|window| Window::parse(&window))
+            .unwrap_or_default();
+        let teaser = cols.next().is_some_and(|teaser| teaser == "true");
+        let reminder_hours = cols.next().and_then(|hours| hours.parse().ok());
+        let ping = cols
+            .next()
+            .and_then(|ping| Ping::parse(&ping))
+            .unwrap_or_default();
+
+        // Blank template columns use the default for that part.
+        let mut template = Template::default();
+        for part in [
+            &mut template.title,
+            &mut template.description,
+            &mut template.field_name,
+            &mut template.field_value,
+            &mut template.empty,
+        ] {
+            if let Some(text) = cols.next().filter(|text| !text.is_empty()) {
+                *part = text;
+            }
+        }
+        let quiet_week = cols
+            .next()
+            .and_then(|quiet| QuietWeek::parse(&quiet))
+            .unwrap_or_default();
+        let group_by_category = cols.next().is_some_and(|group| group == "true");
+        let filter = CategoryFilter {
+            include: cols
+                .next()
+                .map(|include| CategoryFilter::parse(&include))
+                .unwrap_or_default(),
+            exclude: cols
+                .next()
+                .map(|exclude| CategoryFilter::parse(&exclude))
+                .unwrap_or_default(),
+        };
+        let notify_changes = cols.next().is_some_and(|notify| notify == "true");
+
+        Some(Self {
+            id,
+            last_announced,
+            window,
+            teaser,
+            reminder_hours,
+            ping,
+            template,
+            quiet_week,
+            group_by_category,
+            categories: Vec::new(),
+            filter,
+            notify_changes,
+        })
+    }
+
+    fn to_row(&self) -> Vec<String> {
+        vec![
+            self.id.clone(),
+            self.last_announced
+                .map(|date| date.format(DATE_FORMAT).to_string())
+                .unwrap_or_default(),
+            self.window.format(),
+            self.teaser.to_string(),
+            self.reminder_hours
+                .map(|hours| hours.to_string())
+                .unwrap_or_default(),
+            self.ping.format(),
+            self.template.title.clone(),
+            self.template.description.clone(),
+            self.template.field_name.clone(),
+            self.template.field_value.clone(),
+            self.template.empty.clone(),
+            self.quiet_week.format(),
+            self.group_by_category.to_string(),
+            CategoryFilter::format(&self.filter.include),
+            CategoryFilter::format(&self.filter.exclude),
+            self.notify_changes.to_string(),
+        ]
+    }
+
+    /// Style of the heading the event is listed under when grouping.
+    pub fn category_style(&self, event: &Event) -> CategoryStyle {
+        let category = event
+            .category
+            .as_deref()
+            .map(str::trim)
+            .filter(|category| !category.is_empty());
+        match category {
+            Some(category) => self
+                .categories
+                .iter()
+                .find(|style| style.matches(category))
+                .cloned()
+                .unwrap_or_else(|| CategoryStyle::new(category)),
+            None => CategoryStyle::new(CategoryStyle::OTHER),
+        }
+    }
+}
+
+pub async fn load_channels() -> Vec<ChannelConfig> {
+    let csv = load_csv(CHANNELS_CSV).await.unwrap_or_default();
+    let mut channels: Vec<ChannelConfig> = csv
+        .into_iter()
+        .filter_map(ChannelConfig::from_row)
+        .collect();
+
+    let csv = load_csv(CATEGORIES_CSV).await.unwrap_or_default();
+    for (id, style) in csv.into_iter().filter_map(CategoryStyle::from_row) {
+        if let Some(channel) = channels.iter_mut().find(|c| c.id == id) {
+            channel.categories.push(style);
+        }
+    }
+
+    channels
+}
+
+pub async fn save_channels(channels: &[ChannelConfig]) {
+    if let Err(e) = write_csv(
+        &channels.iter().map(ChannelConfig::to_row).collect(),
+        CHANNELS_CSV,
+    )
+    .await
+    {
+        eprintln!("{e}");
+    }
+
+    let categories = channels
+        .iter()
+        .flat_map(|c| c.categories.iter().map(|style| style.to_row(&c.id)))
+        .collect();
+    if let Err(e) = write_csv(&categories, CATEGORIES_CSV).await {
+        eprintln!("{e}");
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use chrono::NaiveDate;
+
+    use crate::events::Event;
+
+    use super::{
+        CategoryFilter, CategoryStyle, ChannelConfig, ChannelSetting, Ping, QuietWeek, Window,
+    };
+
+    #[test]
+    fn test_row_round_trip() {
+        let mut config = ChannelConfig::new("1234".to_string());
+        assert_eq!(
+            ChannelConfig::from_row(config.to_row()),
+            Some(config.clone())
+        );
+
+        config.last_announced = NaiveDate::from_ymd_opt(2024, 2, 4);
+        config.window = Window::RestOfMonth;
+        config.teaser = true;
+        config.reminder_hours = Some(24);
+        config.ping = Ping::Role("5678".to_string());
+        config.template.title = "What's on, {{ start }}".to_string();
+        config.quiet_week = QuietWeek::NextEvent;
+        config.filter.include = vec!["Poetry".to_string(), "Open Mic".to_string()];
+        let row = config.to_row();
+        assert_eq!(
+            row[..7],
+            [
+                "1234",
+                "2024-02-04",
+                "month",
+                "true",
+                "24",
+                "role:5678",
+                "What's on, {{ start }}"
+            ]
+        );
+        assert_eq!(row.len(), 16);
+        assert_eq!(row[11], "next");
+        assert_eq!(row[13], "Poetry,Open Mic");
+        assert_eq!(ChannelConfig::from_row(config.to_row()), Some(config));
+    }
+
+    #[test]
+    fn test_legacy_row() {
+        assert_eq!(
+            ChannelConfig::from_row(vec!["1234".to_string()]),
+            Some(ChannelConfig::new("1234".to_string()))
+        );
+        assert_eq!(ChannelConfig::from_row(vec![String::new()]), None);
+    }
+
+    #[test]
+    fn test_window_last_day() {
+        let today = NaiveDate::from_ymd_opt(2024, 2, 4).unwrap();
+        assert_eq!(
+            Window::Days(7).last_day(today),
+            NaiveDate::from_ymd_opt(2024, 2, 11)
+        );
+        assert_eq!(
+            Window::Days(3).last_day(today),
+            NaiveDate::from_ymd_opt(2024, 2, 7)
+        );
+        assert_eq!(
+            Window::RestOfMonth.last_day(today),
+            NaiveDate::from_ymd_opt(2024, 2, 29)
+        );
+        assert_eq!(
+            Window::RestOfMonth.last_day(NaiveDate::from_ymd_opt(2024, 12, 31).unwrap()),
+            NaiveDate::from_ymd_opt(2024, 12, 31)
+        );
+    }
+
+    #[test]
+    fn test_window_parse() {
+        assert_eq!(Window::parse("14"), Some(Window::Days(14)));
+        assert_eq!(Window::parse("month"), Some(Window::RestOfMonth));
+        assert_eq!(Window::parse("0"), None);
+        assert_eq!(Window::parse("fortnight"), None);
+    }
+
+    #[test]
+    fn test_ping() {
+        assert_eq!(Ping::parse("none"), Some(Ping::Nobody));
+        assert_eq!(Ping::parse("role:"), None);
+        assert_eq!(Ping::Role("1".to_string()).content().unwrap(), "<@&1>");
+        assert_eq!(Ping::Nobody.content(), None);
+        assert_eq!(
+            serde_json::to_value(Ping::Role("1".to_string()).allowed_mentions()).unwrap(),
+            serde_json::json!({ "parse": [], "roles": ["1"] })
+        );
+        assert_eq!(
+            serde_json::to_value(Ping::Here.allowed_mentions()).unwrap(),
+            serde_json::json!({ "parse": ["everyone"] })
+        );
+    }
+
+    #[test]
+    fn test_category_style() {
+        let mut config = ChannelConfig::new("1234".to_string());
+        config.apply(ChannelSetting::Category(CategoryStyle {
+            name: "Workshops".to_string(),
+            emoji: Some("✍️".to_string()),
+            colour: CategoryStyle::parse_colour("#FF8800"),
+        }));
+
+        let event = |category: Option<&str>| Event {
+            category: category.map(str::to_string),
+            ..Event::new("Event", "06 Feb 2024")
+        };
+        let style = config.category_style(&event(Some(" workshops")));
+        assert_eq!(style.heading(), "✍️ Workshops");
+        assert_eq!(style.colour, Some(0xff8800));
+        assert_eq!(
+            config.category_style(&event(Some("Socials"))).heading(),
+            "Socials"
+        );
+        assert_eq!(config.category_style(&event(None)).heading(), "Other");
+
+        let row = style.to_row(&config.id);
+        assert_eq!(row, vec!["1234", "Workshops", "✍️", "#ff8800"]);
+        assert_eq!(
+            CategoryStyle::from_row(row),
+            Some((config.id.clone(), style))
+        );
+        assert_eq!(CategoryStyle::parse_colour("orange"), None);
+
+        config.apply(ChannelSetting::Category(CategoryStyle::new("WORKSHOPS")));
+        assert!(config.categories.is_empty());
+    }
+
+    #[test]
+    fn test_category_filter() {
+        let event = |category: Option<&str>| Event {
+            category: category.map(str::to_string),
+            ..Event::new("Event", "06 Feb 2024")
+        };
+        let mut filter = CategoryFilter::default();
+        assert!(filter.matches(&event(None)));
+
+        filter.include = CategoryFilter::parse(" Poetry, open mic ,");
+        assert_eq!(filter.include, vec!["Poetry", "open mic"]);
+        assert!(filter.matches(&event(Some("poetry "))));
+        assert!(filter.matches(&event(Some("Open Mic"))));
+        assert!(!filter.matches(&event(Some("Prose"))));
+        assert!(!filter.matches(&event(None)));
+
+        filter.include.clear();
+        filter.exclude = vec!["Prose".to_string()];
+        assert!(!filter.matches(&event(Some("prose"))));
+        assert!(filter.matches(&event(None)));
+    }
+}
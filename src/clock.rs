@@ -0,0 +1,96 @@
+use std::sync::OnceLock;
+
+use chrono::{DateTime, Utc};
+use chrono_tz::Tz;
+use futures::future::BoxFuture;
+
+use crate::consts::TIMEZONE;
+
+/// The configured time zone, or the host's if none is configured, falling
+/// back to UTC if it can't be told.
+pub fn timezone() -> Tz {
+    static HOST: OnceLock<Tz> = OnceLock::new();
+    TIMEZONE.unwrap_or_else(|| {
+        *HOST.get_or_init(|| {
+            std::env::var("TZ")
+                .ok()
+                .and_then(|tz| tz.trim_start_matches(':').parse().ok())
+                .or_else(|| iana_time_zone::get_timezone().ok()?.parse().ok())
+                .unwrap_or_else(|| {
+                    eprintln!("Couldn't tell the host's time zone, using UTC.");
+                    chrono_tz::UTC
+                })
+        })
+    })
+}
+
+/// Source of the current time for the announcer, so that scheduling can be
+/// driven by a fake clock in tests.
+pub trait Clock: Send + Sync {
+    fn now(&self) -> DateTime<Tz>;
+
+    /// Resolve once the clock reads at least `when`.
+    fn sleep_until(&self, when: DateTime<Tz>) -> BoxFuture<'_, ()>;
+}
+
+pub struct SystemClock {
+    tz: Tz,
+}
+
+impl SystemClock {
+    pub fn new() -> Self {
+        Self { tz: timezone() }
+    }
+}
+
+impl Clock for SystemClock {
+    fn now(&self) -> DateTime<Tz> {
+        Utc::now().with_timezone(&self.tz)
+    }
+
+    fn sleep_until(&self, when: DateTime<Tz>) -> BoxFuture<'_, ()> {
+        Box::pin(async move {
+            if let Ok(duration) = when.signed_duration_since(self.now()).to_std() {
+                tokio::time::sleep(duration).await;
+            }
+        })
+    }
+}
+
+/// Clock which only moves when told to. Sleepers are woken whenever the time
+/// is changed.
+#[cfg(test)]
+pub struct FakeClock {
+    now: tokio::sync::watch::Sender<DateTime<Tz>>,
+}
+
+#[cfg(test)]
+impl FakeClock {
+    pub fn new(now: DateTime<Tz>) -> Self {
+        Self {
+            now: tokio::sync::watch::channel(now).0,
+        }
+    }
+
+    pub fn set(&self, now: DateTime<Tz>) {
+        self.now.send_replace(now);
+    }
+
+    pub fn advance(&self, duration: chrono::Duration) {
+        self.now.send_modify(|now| *now += duration);
+    }
+}
+
+#[cfg(test)]
+impl Clock for FakeClock {
+    fn now(&self) -> DateTime<Tz> {
+        *self.now.borrow()
+    }
+
+    fn sleep_until(&self, when: DateTime<Tz>) -> BoxFuture<'_, ()> {
+        let mut recv = self.now.subscribe();
+        Box::pin(async move {
+            recv.wait_for(|now| *now >= when).await.ok();
+        })
+    }
+}
@@ -0,0 +1,543 @@
+use chrono::DateTime;
+use chrono_tz::Tz;
+
+use crate::{
+    announcer::{digest, quiet_digest},
+    channels::{
+        load_channels, CategoryFilter, CategoryStyle, ChannelConfig, ChannelSetting, Window,
+    },
+    clock::Clock,
+    consts::ORGANISER_ROLE,
+    discord::{
+        self, ApplicationCommandOption, ApplicationCommandOptionType, MessageComponent,
+        Permissions, TextInputStyle,
+    },
+    err,
+    events::{load_announcements, Event},
+    msg,
+    template::Template,
+    Result,
+};
+
+/// How long to wait for events to load before deferring the response, leaving
+/// a margin within Discord's three second limit.
+const DEFER_AFTER: std::time::Duration = std::time::Duration::from_secs(2);
+
+/// Custom id of the template form.
+pub const TEMPLATE_ROUTE: &str = "template";
+
+/// Discord's limit on the length of a text input.
+const MAX_TEMPLATE_LENGTH: i32 = 4000;
+
+const DEFAULT_DAYS: u32 = 7;
+const MAX_DAYS: u32 = 90;
+
+/// Permissions needed to see organiser commands, unless a server overrides
+/// them to allow the organiser role.
+pub const ORGANISER_PERMISSIONS: Permissions = Permissions::MANAGE_CHANNELS;
+
+/// Whether the user who invoked the interaction may configure the bot, either
+/// with the Manage Channels permission or the organiser role.
+pub fn is_organiser(interaction: &discord::Interaction) -> bool {
+    interaction.member().is_some_and(|member| {
+        let permissions = member.permissions();
+        permissions.contains(ORGANISER_PERMISSIONS)
+            || permissions.contains(Permissions::ADMINISTRATOR)
+            || ORGANISER_ROLE.is_some_and(|role| member.roles().iter().any(|r| r == role))
+    })
+}
+
+/// Filters for the events command.
+#[derive(Debug, PartialEq)]
+struct EventQuery {
+    days: u32,
+    category: Option<String>,
+    location: Option<String>,
+}
+
+impl EventQuery {
+    fn from_interaction(interaction: &discord::Interaction) -> Self {
+        Self {
+            days: interaction
+                .option_int("days")
+                .and_then(|days| u32::try_from(days).ok())
+                .filter(|days| *days > 0)
+                .unwrap_or(DEFAULT_DAYS)
+                .min(MAX_DAYS),
+            category: interaction.option_str("category").map(str::to_lowercase),
+            location: interaction.option_str("location").map(str::to_lowercase),
+        }
+    }
+
+    fn matches(&self, event: &Event) -> bool {
+        let category = self.category.as_ref().is_none_or(|category| {
+            event
+                .category
+                .as_ref()
+                .is_some_and(|c| c.trim().to_lowercase() == *category)
+        });
+        let location = self
+            .location
+            .as_ref()
+            .is_none_or(|location| event.location.to_lowercase().contains(location));
+
+        category && location
+    }
+}
+
+pub fn events_options() -> Vec<ApplicationCommandOption> {
+    vec![
+        ApplicationCommandOption::new(
+            ApplicationCommandOptionType::Integer,
+            "days",
+            "How many days ahead to look.",
+        )
+        .min_value(1.0)
+        .max_value(MAX_DAYS.into()),
+        ApplicationCommandOption::new(
+            ApplicationCommandOptionType::String,
+            "category",
+            "Only show events in this category.",
+        )
+        .autocomplete(true),
+        ApplicationCommandOption::new(
+            ApplicationCommandOptionType::String,
+            "location",
+            "Only show events at locations matching this.",
+        )
+        .autocomplete(true),
+        ApplicationCommandOption::new(
+            ApplicationCommandOptionType::Boolean,
+            "public",
+            "Show the reply to everyone in the channel.",
+        ),
+    ]
+}
+
+/// Digest of events matching the query, as many pages as fit in a reply. On
+/// failure, returns the text to reply with instead.
+fn events_embeds(
+    events: Result<Vec<Event>>,
+    query: &EventQuery,
+    mut channel: ChannelConfig,
+    now: &DateTime<Tz>,
+) -> Result<Vec<discord::Embed>> {
+    let mut events = events.map_err(|e| {
+        eprintln!("Failed to load events: {e}");
+        msg("Failed to load events, please try again later.")
+    })?;
+    events.retain(|event| query.matches(event));
+
+    // The query picks the events, not the channel's announcement settings.
+    channel.window = Window::Days(query.days);
+    channel.teaser = false;
+    channel.filter = CategoryFilter::default();
+    let embeds = discord::Embed::fit_message(digest(events, now, &channel));
+    if embeds.is_empty() {
+        return err("No upcoming events found.");
+    }
+    Ok(embeds)
+}
+
+/// Configuration of the channel the interaction was in, if registered.
+async fn registered_channel(interaction: &discord::Interaction) -> Option<ChannelConfig> {
+    let channel = interaction.channel()?;
+    load_channels().await.into_iter().find(|c| &c.id == channel)
+}
+
+/// Reply with embeds built from the events once they load. If they take too
+/// long, the response is deferred and edited once they arrive.
+async fn reply_with_events<F>(
+    interaction: &discord::Interaction,
+    flags: discord::MessageFlags,
+    build: F,
+) -> discord::InteractionResponse
+where
+    F: FnOnce(Result<Vec<Event>>) -> Result<Vec<discord::Embed>> + Send + 'static,
+{
+    let mut load = tokio::spawn(load_announcements());
+    match tokio::time::timeout(DEFER_AFTER, &mut load).await {
+        Ok(events) => match build(events.map_err(msg).and_then(|events| events)) {
+            Ok(embeds) => discord::InteractionResponse::embeds(embeds).flags(flags),
+            Err(text) => discord::InteractionResponse::message(text).flags(flags),
+        },
+        Err(_) => interaction.defer(flags, async move {
+            match build(load.await.map_err(msg).and_then(|events| events)) {
+                Ok(embeds) => discord::WebhookMessage::embeds(embeds),
+                Err(text) => discord::WebhookMessage::content(text),
+            }
+        }),
+    }
+}
+
+/// Reply with upcoming events, formatted as in the channel's digest.
+pub async fn events(
+    interaction: &discord::Interaction,
+    clock: &dyn Clock,
+) -> discord::InteractionResponse {
+    let query = EventQuery::from_interaction(interaction);
+    let flags = if interaction.option_bool("public") == Some(true) {
+        discord::MessageFlags::empty()
+    } else {
+        discord::MessageFlags::EPHEMERAL
+    };
+
+    let channel = match registered_channel(interaction).await {
+        Some(channel) => channel,
+        None => ChannelConfig::new(interaction.channel().cloned().unwrap_or_default()),
+    };
+    let now = clock.now();
+
+    reply_with_events(interaction, flags, move |events| {
+        events_embeds(events, &query, channel, &now)
+    })
+    .await
+}
+
+/// The channel's next digest as it would be posted now, or its quiet week
+/// message if there are no events.
+fn preview_embeds(
+    events: Result<Vec<Event>>,
+    channel: &ChannelConfig,
+    now: &DateTime<Tz>,
+) -> Result<Vec<discord::Embed>> {
+    let events = events.map_err(|e| {
+        eprintln!("Failed to load events: {e}");
+        msg("Failed to load events, please try again later.")
+    })?;
+
+    let embeds = discord::Embed::fit_message(digest(events.clone(), now, channel));
+    if !embeds.is_empty() {
+        return Ok(embeds);
+    }
+    quiet_digest(&events, now, channel)
+        .map(|embed| vec![embed])
+        .ok_or_else(|| {
+            msg("There are no events, so nothing would be posted. Use /announce quiet_week to post a message instead.")
+        })
+}
+
+/// Show the channel's digest, rendered with its template against the current
+/// events, to the organiser alone.
+pub async fn preview(
+    interaction: &discord::Interaction,
+    clock: &dyn Clock,
+) -> discord::InteractionResponse {
+    let Some(channel) = registered_channel(interaction).await else {
+        return discord::InteractionResponse::message("Use /announce in this channel first.")
+            .ephemeral();
+    };
+    let now = clock.now();
+
+    reply_with_events(
+        interaction,
+        discord::MessageFlags::EPHEMERAL,
+        move |events| preview_embeds(events, &channel, &now),
+    )
+    .await
+}
+
+/// Open a form to edit the channel's digest template, filled in with the
+/// current one.
+pub async fn template(interaction: &discord::Interaction) -> discord::InteractionResponse {
+    let Some(channel) = registered_channel(interaction).await else {
+        return discord::InteractionResponse::message("Use /announce in this channel first.")
+            .ephemeral();
+    };
+
+    let template = channel.template;
+    let input = |id: &str, label: &str, value: String| {
+        MessageComponent::text_input(TextInputStyle::Paragraph, id, label)
+            .length(0, MAX_TEMPLATE_LENGTH)
+            .required(false)
+            .placeholder("Leave blank for the default.")
+            .value(value)
+    };
+    discord::InteractionResponse::modal(
+        TEMPLATE_ROUTE,
+        "Digest Template",
+        vec![
+            input("title", "Title", template.title),
+            input("description", "Description", template.description),
+            input("field_name", "Event name", template.field_name),
+            input("field_value", "Event details", template.field_value),
+            input("empty", "Message when there are no events", template.empty),
+        ],
+    )
+}
+
+pub fn category_options() -> Vec<ApplicationCommandOption> {
+    vec![
+        ApplicationCommandOption::new(
+            ApplicationCommandOptionType::String,
+            "category",
+            "Category to style, as written in the events sheet.",
+        )
+        .required(true)
+        .autocomplete(true),
+        ApplicationCommandOption::new(
+            ApplicationCommandOptionType::String,
+            "emoji",
+            "Emoji shown before the category's heading.",
+        ),
+        ApplicationCommandOption::new(
+            ApplicationCommandOptionType::String,
+            "colour",
+            "Colour of the category's embeds in hex, e.g. #ff8800.",
+        ),
+    ]
+}
+
+/// Style from the options of a category command.
+fn category_style(interaction: &discord::Interaction) -> Result<CategoryStyle> {
+    let Some(name) = interaction
+        .option_str("category")
+        .filter(|name| !name.trim().is_empty())
+    else {
+        return err("Choose a category to style.");
+    };
+
+    let mut style = CategoryStyle::new(name);
+    style.emoji = interaction
+        .option_str("emoji")
+        .map(str::trim)
+        .filter(|emoji| !emoji.is_empty())
+        .map(str::to_string);
+    if let Some(colour) = interaction.option_str("colour") {
+        match CategoryStyle::parse_colour(colour) {
+            Some(colour) => style.colour = Some(colour),
+            None => return err(format!("Invalid colour: {colour}, use hex like #ff8800.")),
+        }
+    }
+    Ok(style)
+}
+
+/// Setting for the category heading style given to a category command in a
+/// registered channel.
+pub async fn category(interaction: &discord::Interaction) -> Result<ChannelSetting> {
+    if registered_channel(interaction).await.is_none() {
+        return err("Use /announce in this channel first.");
+    }
+    category_style(interaction).map(ChannelSetting::Category)
+}
+
+/// Template from a submitted template form, if it renders. Blank parts use
+/// the default.
+pub fn template_from_modal(
+    interaction: &discord::Interaction,
+    now: &DateTime<Tz>,
+) -> Result<Template> {
+    let mut template = Template::default();
+    for (id, part) in [
+        ("title", &mut template.title),
+        ("description", &mut template.description),
+        ("field_name", &mut template.field_name),
+        ("field_value", &mut template.field_value),
+        ("empty", &mut template.empty),
+    ] {
+        if let Some(text) = interaction.text_value(id).filter(|t| !t.trim().is_empty()) {
+            *part = text.to_string();
+        }
+    }
+    template.validate(now)?;
+    Ok(template)
+}
+
+#[cfg(test)]
+mod test {
+    use chrono::TimeZone;
+
+    use crate::{
+        channels::{ChannelConfig, QuietWeek},
+        discord::Interaction,
+        events::Event,
+        template::Template,
+    };
+
+    use super::{events_embeds, is_organiser, preview_embeds, template_from_modal, EventQuery};
+
+    fn interaction(member: &str) -> Interaction {
+        serde_json::from_str(&format!(
+            r#"{{
+                "type": 2,
+                "id": "1",
+                "application_id": "2",
+                "channel_id": "3",
+                "token": "token",
+                "version": 1,
+                "entitlements": [],
+                "member": {member}
+            }}"#
+        ))
+        .unwrap()
+    }
+
+    fn event(name: &str, date: &str, location: &str, category: Option<&str>) -> Event {
+        Event {
+            location: location.to_string(),
+            category: category.map(str::to_string),
+            ..Event::new(name, date)
+        }
+    }
+
+    #[test]
+    fn test_is_organiser() {
+        let member = |permissions: &str| {
+            format!(
+                r#"{{
+                    "user": {{ "id": "4", "username": "member" }},
+                    "roles": ["5"],
+                    "permissions": "{permissions}"
+                }}"#
+            )
+        };
+
+        let manager = interaction(&member("16"));
+        assert_eq!(manager.user().unwrap().id, "4");
+        assert!(is_organiser(&manager));
+        assert!(is_organiser(&interaction(&member("8"))));
+        assert!(!is_organiser(&interaction(&member("1024"))));
+        assert!(!is_organiser(&interaction("null")));
+    }
+
+    #[test]
+    fn test_query_matches() {
+        let workshop = event(
+            "Workshop",
+            "06 Feb 2024",
+            "Main Library",
+            Some("Workshops "),
+        );
+        let social = event("Social", "06 Feb 2024", "The Pub", None);
+
+        let mut query = EventQuery {
+            days: 7,
+            category: None,
+            location: None,
+        };
+        assert!(query.matches(&workshop) && query.matches(&social));
+
+        query.category = Some("workshops".to_string());
+        assert!(query.matches(&workshop) && !query.matches(&social));
+
+        query.category = None;
+        query.location = Some("pub".to_string());
+        assert!(!query.matches(&workshop) && query.matches(&social));
+    }
+
+    #[test]
+    fn test_events_embeds() {
+        let now = chrono_tz::UTC
+            .with_ymd_and_hms(2024, 2, 4, 12, 0, 0)
+            .unwrap();
+        let events = vec![
+            event("Tuesday", "06 Feb 2024", "Library", None),
+            event("Next Month", "06 Mar 2024", "Library", None),
+        ];
+        let query = EventQuery {
+            days: 3,
+            category: None,
+            location: None,
+        };
+
+        let embeds = events_embeds(
+            Ok(events.clone()),
+            &query,
+            ChannelConfig::new(String::new()),
+            &now,
+        )
+        .unwrap();
+        let embed = &embeds[0];
+        assert_eq!(embed.title(), Some("Events in the Next 3 Days"));
+        assert_eq!(embed.fields().len(), 1);
+
+        let query = EventQuery {
+            location: Some("pub".to_string()),
+            ..query
+        };
+        assert_eq!(
+            events_embeds(Ok(events), &query, ChannelConfig::new(String::new()), &now).unwrap_err(),
+            "No upcoming events found."
+        );
+
+        // Categories the channel leaves out of announcements can be asked for.
+        let mut channel = ChannelConfig::new(String::new());
+        channel.filter.exclude = vec!["prose".to_string()];
+        let reading = vec![event("Reading", "06 Feb 2024", "Library", Some("Prose"))];
+        let query = EventQuery {
+            category: Some("prose".to_string()),
+            location: None,
+            ..query
+        };
+        assert_eq!(
+            events_embeds(Ok(reading), &query, channel, &now).unwrap()[0]
+                .fields()
+                .len(),
+            1
+        );
+
+        assert!(events_embeds(
+            Err("offline".to_string()),
+            &query,
+            ChannelConfig::new(String::new()),
+            &now
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn test_preview_embeds() {
+        let now = chrono_tz::UTC
+            .with_ymd_and_hms(2024, 2, 4, 12, 0, 0)
+            .unwrap();
+        let mut channel = ChannelConfig::new(String::new());
+        channel.template.empty = "Nothing on until {{ end }}.".to_string();
+
+        let embeds = preview_embeds(
+            Ok(vec![event("Tuesday", "06 Feb 2024", "Library", None)]),
+            &channel,
+            &now,
+        )
+        .unwrap();
+        assert_eq!(embeds[0].fields().len(), 1);
+
+        assert!(preview_embeds(Ok(Vec::new()), &channel, &now).is_err());
+        channel.quiet_week = QuietWeek::Message;
+        let embeds = preview_embeds(Ok(Vec::new()), &channel, &now).unwrap();
+        assert_eq!(embeds[0].title(), Some("Events this Week"));
+        assert_eq!(
+            embeds[0].description(),
+            Some("Nothing on until Sunday 11/02.")
+        );
+    }
+
+    #[test]
+    fn test_template_from_modal() {
+        let now = chrono_tz::UTC
+            .with_ymd_and_hms(2024, 2, 4, 12, 0, 0)
+            .unwrap();
+        let modal = |title: &str| -> Interaction {
+            serde_json::from_value(serde_json::json!({
+                "type": 5,
+                "id": "1",
+                "application_id": "2",
+                "token": "token",
+                "version": 1,
+                "entitlements": [],
+                "data": {
+                    "custom_id": "template",
+                    "components": [
+                        { "type": 1, "components": [{ "type": 4, "custom_id": "title", "value": title }] },
+                        { "type": 1, "components": [{ "type": 4, "custom_id": "empty", "value": " " }] },
+                    ]
+                }
+            }))
+            .unwrap()
+        };
+
+        let template = template_from_modal(&modal("{{ count }} events"), &now).unwrap();
+        assert_eq!(template.title, "{{ count }} events");
+        assert_eq!(template.empty, Template::default().empty);
+        assert!(template_from_modal(&modal("{{ count"), &now).is_err());
+    }
+}
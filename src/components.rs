@@ -0,0 +1,117 @@
+use futures::future::BoxFuture;
+
+use crate::{
+    discord::{Interaction, InteractionResponse},
+    rsvp, submissions,
+};
+
+/// Separates the route of a custom id from the arguments passed to its
+/// handler, e.g. `route:args`.
+const SEPARATOR: char = ':';
+
+/// Handles a component interaction, given the arguments from its custom id.
+type Handler = for<'a> fn(&'a Interaction, &'a str) -> BoxFuture<'a, InteractionResponse>;
+
+/// Handlers for component and modal interactions by custom id route.
+const ROUTES: &[(&str, Handler)] = &[
+    (rsvp::ROUTE, |interaction, args| {
+        Box::pin(rsvp::handle(interaction, args))
+    }),
+    (submissions::ROUTE, |interaction, args| {
+        Box::pin(submissions::handle(interaction, args))
+    }),
+    (submissions::REVIEW_ROUTE, |interaction, args| {
+        Box::pin(submissions::review(interaction, args))
+    }),
+];
+
+fn route<'a>(routes: &[(&str, Handler)], custom_id: &'a str) -> Option<(Handler, &'a str)> {
+    let (prefix, args) = custom_id.split_once(SEPARATOR).unwrap_or((custom_id, ""));
+    routes
+        .iter()
+        .find(|(route, _)| *route == prefix)
+        .map(|(_, handler)| (*handler, args))
+}
+
+/// Pass a component or modal interaction to the handler for its custom id.
+pub async fn dispatch(interaction: &Interaction) -> InteractionResponse {
+    match interaction.custom_id().and_then(|id| route(ROUTES, id)) {
+        Some((handler, args)) => handler(interaction, args).await,
+        None => InteractionResponse::message("Unrecognised component.").ephemeral(),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::discord::{ButtonStyle, Interaction, InteractionResponse, MessageComponent};
+
+    use super::{dispatch, route, Handler};
+
+    const ROUTES: &[(&str, Handler)] = &[
+        ("a", |_, args| {
+            Box::pin(async move { InteractionResponse::message(format!("a {args}")) })
+        }),
+        ("b", |_, _| {
+            Box::pin(async { InteractionResponse::message("b") })
+        }),
+    ];
+
+    #[test]
+    fn test_route() {
+        assert_eq!(route(ROUTES, "a:1:2").map(|(_, args)| args), Some("1:2"));
+        assert_eq!(route(ROUTES, "b").map(|(_, args)| args), Some(""));
+        assert!(route(ROUTES, "c:1").is_none());
+        assert!(route(ROUTES, "ab:1").is_none());
+    }
+
+    #[actix_web::test]
+    async fn test_dispatch() {
+        let interaction = |custom_id: &str| -> Interaction {
+            serde_json::from_value(serde_json::json!({
+                "type": 3,
+                "id": "1",
+                "application_id": "2",
+                "token": "token",
+                "version": 1,
+                "entitlements": [],
+                "user": { "id": "3", "username": "user" },
+                "data": { "custom_id": custom_id, "component_type": 2 }
+            }))
+            .unwrap()
+        };
+        let content = |resp: InteractionResponse| {
+            serde_json::to_value(resp).unwrap()["data"]["content"].clone()
+        };
+
+        assert_eq!(
+            content(dispatch(&interaction("rsvp:abc:maybe?")).await),
+            "Unrecognised response."
+        );
+        assert_eq!(
+            content(dispatch(&interaction("review:abc:approve")).await),
+            "Only organisers can review events."
+        );
+        assert_eq!(
+            content(dispatch(&interaction("rsvps:abc:going")).await),
+            "Unrecognised component."
+        );
+    }
+
+    #[test]
+    fn test_component_serialisation() {
+        let row = MessageComponent::action_row(vec![
+            MessageComponent::button(ButtonStyle::Success, "Yes", "a:yes"),
+            MessageComponent::button(ButtonStyle::Danger, "No", "a:no").disabled(true),
+        ]);
+        assert_eq!(
+            serde_json::to_value(&row).unwrap(),
+            serde_json::json!({
+                "type": 1,
+                "components": [
+                    { "type": 2, "custom_id": "a:yes", "style": 3, "label": "Yes" },
+                    { "type": 2, "custom_id": "a:no", "style": 4, "label": "No", "disabled": true },
+                ]
+            })
+        );
+    }
+}
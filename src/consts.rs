@@ -10,3 +10,25 @@ pub const TOKEN: &str = "Bot BOT-TOKEN-HERE";
 pub const APPLICATION_ID: &str = "0000000000000000000";
 
 pub const EVENTS_SHEET_CSV: &str = "https://file.csv";
+
+/// Local CSV file in the same layout as the sheet to load events from instead,
+/// which RSVPs are written back to.
+pub const EVENTS_FILE: Option<&str> = None;
+
+/// Logo shown on digests, if any.
+pub const LOGO_URL: Option<&str> = None;
+
+/// Time zone announcements are scheduled in and event dates are read in. If
+/// unset, the host's zone is used, from `TZ` or the system setting.
+pub const TIMEZONE: Option<chrono_tz::Tz> = None;
+
+pub const CATCH_UP_GRACE_HOURS: i64 = 24;
+
+pub const ORGANISER_ROLE: Option<&str> = None;
+
+/// Channel where organisers approve events submitted with /event add. If
+/// unset, submissions are added straight away.
+pub const MODERATION_CHANNEL: Option<&str> = None;
+
+/// Server to mirror upcoming events into as scheduled events, if any.
+pub const SCHEDULED_EVENTS_GUILD: Option<&str> = None;
@@ -0,0 +1,511 @@
+use std::sync::Arc;
+
+use chrono::DateTime;
+use chrono_tz::Tz;
+use serde::Serialize;
+use tokio::sync::Mutex;
+
+use crate::{
+    announcer::{digest_pages, quiet_digest, send_message, upcoming, CreateMessageRequest},
+    channels::ChannelConfig,
+    clock::{timezone, Clock},
+    csv::{load_csv, write_csv},
+    discord,
+    events::{load_announcements, stable_hash, start_time, Event},
+    req, rsvp, Result,
+};
+
+const POSTED_CSV: &str = "posted.csv";
+
+/// How often to check posted digests against the events.
+const POLL_MINUTES: i64 = 30;
+
+/// Held while reading and rewriting the posted file.
+static LOCK: Mutex<()> = Mutex::const_new(());
+
+/// A digest page posted to a channel as a message, stored as a row of the
+/// posted CSV followed by the name and date of each event it lists.
+#[derive(Clone, Debug, PartialEq)]
+struct PostedPage {
+    channel: discord::Snowflake,
+
+    /// Time the digest was built for, so that it can be rebuilt the same way.
+    slot: DateTime<Tz>,
+
+    message: discord::Snowflake,
+
+    /// Hash of the page as posted, to tell when it needs editing.
+    fingerprint: String,
+
+    events: Vec<(String, String)>,
+}
+
+impl PostedPage {
+    fn new(
+        channel: &discord::Snowflake,
+        slot: &DateTime<Tz>,
+        message: discord::Snowflake,
+        embeds: &[discord::Embed],
+        events: &[Event],
+    ) -> Self {
+        Self {
+            channel: channel.clone(),
+            slot: *slot,
+            message,
+            fingerprint: fingerprint(embeds),
+            events: events
+                .iter()
+                .map(|e| (e.name.clone(), e.date.clone()))
+                .collect(),
+        }
+    }
+
+    fn from_row(row: Vec<String>) -> Option<Self> {
+        let mut cols = row.into_iter();
+        let channel = cols.next()?;
+        let slot = DateTime::parse_from_rfc3339(&cols.next()?)
+            .ok()?
+            .with_timezone(&timezone());
+        let message = cols.next()?;
+        let fingerprint = cols.next()?;
+        let events = cols
+            .collect::<Vec<String>>()
+            .chunks_exact(2)
+            .map(|pair| (pair[0].clone(), pair[1].clone()))
+            .collect();
+        Some(Self {
+            channel,
+            slot,
+            message,
+            fingerprint,
+            events,
+        })
+    }
+
+    fn to_row(&self) -> Vec<String> {
+        let mut row = vec![
+            self.channel.clone(),
+            self.slot.to_rfc3339(),
+            self.message.clone(),
+            self.fingerprint.clone(),
+        ];
+        for (name, date) in &self.events {
+            row.push(name.clone());
+            row.push(date.clone());
+        }
+        row
+    }
+}
+
+fn fingerprint(embeds: &[discord::Embed]) -> String {
+    stable_hash(&serde_json::to_string(embeds).unwrap_or_default())
+}
+
+async fn load_posted() -> Vec<PostedPage> {
+    let csv = load_csv(POSTED_CSV).await.unwrap_or_default();
+
+    csv.into_iter().filter_map(PostedPage::from_row).collect()
+}
+
+async fn save_posted(posted: &[PostedPage]) {
+    if let Err(e) = write_csv(&posted.iter().map(PostedPage::to_row).collect(), POSTED_CSV).await {
+        eprintln!("{e}");
+    }
+}
+
+/// Remember a page of a digest posted to a channel, replacing those posted
+/// for earlier weeks.
+pub async fn record(
+    channel: &discord::Snowflake,
+    slot: &DateTime<Tz>,
+    message: discord::Snowflake,
+    embeds: &[discord::Embed],
+    events: &[Event],
+) {
+    let _lock = LOCK.lock().await;
+    let mut posted = load_posted().await;
+    posted.retain(|p| p.channel != *channel || p.slot == *slot);
+    posted.push(PostedPage::new(channel, slot, message, embeds, events));
+    save_posted(&posted).await;
+}
+
+/// Number of pages of the digest for `slot` already posted to a channel.
+pub async fn posted_pages(channel: &discord::Snowflake, slot: &DateTime<Tz>) -> usize {
+    let _lock = LOCK.lock().await;
+    load_posted()
+        .await
+        .iter()
+        .filter(|p| p.channel == *channel && p.slot == *slot)
+        .count()
+}
+
+/// How to bring a posted page in line with the digest as it would be now.
+#[derive(Debug, PartialEq)]
+enum Action {
+    Keep,
+    Edit,
+    Post,
+    Delete,
+}
+
+/// Actions for each page, pairing posted pages with current ones in order.
+fn plan(posted: &[&PostedPage], pages: &[(Vec<discord::Embed>, &[Event])]) -> Vec<Action> {
+    (0..posted.len().max(pages.len()))
+        .map(|i| match (posted.get(i), pages.get(i)) {
+            (Some(old), Some((embeds, _))) if old.fingerprint == fingerprint(embeds) => {
+                Action::Keep
+            }
+            (Some(_), Some(_)) => Action::Edit,
+            (None, _) => Action::Post,
+            (Some(_), None) => Action::Delete,
+        })
+        .collect()
+}
+
+/// Events listed on posted pages which are no longer in the events on the
+/// same date, so that a recurring event missing a week is noticed. Each comes
+/// with the event it moved to, if one of the same name is newly listed on the
+/// current pages, the closest if there are several. Events which have
+/// already started are left out, as their rows may simply have been removed.
+fn cancelled<'a, 'b>(
+    posted: &[&'a PostedPage],
+    events: &[Event],
+    listed: &[&'b Event],
+    now: &DateTime<Tz>,
+) -> Vec<(&'a (String, String), Option<&'b Event>)> {
+    let tz = now.timezone();
+    let was_posted = |event: &Event| {
+        posted
+            .iter()
+            .flat_map(|page| &page.events)
+            .any(|(name, date)| event.name == *name && event.date == *date)
+    };
+    let mut moved_to: Vec<&Event> = listed.iter().copied().filter(|e| !was_posted(e)).collect();
+
+    let mut cancelled = Vec::new();
+    for event in posted.iter().flat_map(|page| &page.events) {
+        let (name, date) = event;
+        let start = start_time(date, &tz);
+        if events.iter().any(|e| e.name == *name && e.date == *date)
+            || start.is_none_or(|start| start < *now)
+        {
+            continue;
+        }
+        let to = moved_to
+            .iter()
+            .enumerate()
+            .filter(|(_, e)| e.has_name(name))
+            .min_by_key(|(_, e)| {
+                e.start_time(&tz)
+                    .zip(start)
+                    .map_or(i64::MAX, |(a, b)| (a - b).num_seconds().abs())
+            })
+            .map(|(i, _)| i);
+        cancelled.push((event, to.map(|i| moved_to.remove(i))));
+    }
+    cancelled
+}
+
+#[derive(Serialize)]
+struct EditMessageRequest {
+    embeds: Vec<discord::Embed>,
+    components: Vec<discord::MessageComponent>,
+}
+
+async fn edit_page(
+    channel: &discord::Snowflake,
+    message: &discord::Snowflake,
+    mut embeds: Vec<discord::Embed>,
+    events: &[Event],
+) -> Result<()> {
+    rsvp::add_counts(&mut embeds, events).await;
+    let body = EditMessageRequest {
+        embeds,
+        components: rsvp::buttons(events),
+    };
+    let uri = req::api_uri(format!("/channels/{channel}/messages/{message}"));
+    req::patch::<_, _, discord::Message>(uri, body)
+        .await
+        .map(|_| ())
+}
+
+async fn post_page(
+    channel: &discord::Snowflake,
+    embeds: Vec<discord::Embed>,
+    events: &[Event],
+) -> Result<discord::Message> {
+    let body = CreateMessageRequest {
+        embeds: Some(embeds),
+        components: Some(rsvp::buttons(events)),
+        allowed_mentions: Some(discord::AllowedMentions::none()),
+        ..Default::default()
+    };
+    send_message(body, channel).await
+}
+
+/// Edit, post or delete pages of the channel's digest to match the events,
+/// returning the pages now posted. Pages which fail are left as they were to
+/// be retried.
+async fn correct_channel(
+    channel: &ChannelConfig,
+    posted: Vec<&PostedPage>,
+    events: &[Event],
+    now: &DateTime<Tz>,
+) -> Vec<PostedPage> {
+    let slot = posted[0].slot;
+    let (window, later) = upcoming(events.to_vec(), &slot, channel);
+    let mut pages = digest_pages(&window, &later, &slot, channel);
+    if pages.is_empty() {
+        if let Some(embed) = quiet_digest(events, &slot, channel) {
+            pages.push((vec![embed], &[]));
+        }
+    }
+
+    let mut corrected = Vec::new();
+    let mut replaced = Vec::new();
+    for (i, action) in plan(&posted, &pages).into_iter().enumerate() {
+        let result = match action {
+            Action::Keep => {
+                corrected.push(posted[i].clone());
+                continue;
+            }
+            Action::Edit => {
+                let (embeds, page_events) = &pages[i];
+                edit_page(&channel.id, &posted[i].message, embeds.clone(), page_events)
+                    .await
+                    .map(|()| posted[i].message.clone())
+            }
+            Action::Post => {
+                let (embeds, page_events) = &pages[i];
+                post_page(&channel.id, embeds.clone(), page_events)
+                    .await
+                    .map(|message| message.id().clone())
+            }
+            Action::Delete => {
+                let message = &posted[i].message;
+                req::delete(req::api_uri(format!(
+                    "/channels/{}/messages/{message}",
+                    channel.id
+                )))
+                .await
+                .map(|()| message.clone())
+            }
+        };
+
+        match result {
+            Ok(message) => {
+                if let Some(old) = posted.get(i) {
+                    replaced.push(*old);
+                }
+                if let Some((embeds, page_events)) = pages.get(i) {
+                    corrected.push(PostedPage::new(
+                        &channel.id,
+                        &slot,
+                        message,
+                        embeds,
+                        page_events,
+                    ));
+                }
+            }
+            Err(e) => {
+                eprintln!("Failed to correct digest: {e}");
+                if let Some(old) = posted.get(i) {
+                    corrected.push((*old).clone());
+                }
+            }
+        }
+    }
+
+    // Channels notified of changes already hear about cancellations and moves.
+    let listed: Vec<&Event> = pages.iter().flat_map(|(_, events)| events.iter()).collect();
+    let cancelled = cancelled(&replaced, events, &listed, now);
+    if !cancelled.is_empty() && !channel.notify_changes {
+        let lines: Vec<String> = cancelled
+            .iter()
+            .map(|((name, date), to)| match to {
+                Some(to) => format!("{name} on {date} has moved to {}.", to.date),
+                None => format!("{name} on {date} has been cancelled."),
+            })
+            .collect();
+        let body = CreateMessageRequest {
+            embeds: Some(vec![discord::Embed::new("Correction", &lines.join("\n"))]),
+            allowed_mentions: Some(discord::AllowedMentions::none()),
+            ..Default::default()
+        };
+        if let Err(e) = send_message(body, &channel.id).await {
+            eprintln!("Failed to post correction: {e}");
+        }
+    }
+
+    corrected
+}
+
+/// Bring this week's posted digests in line with the events.
+async fn correct(channels: &[ChannelConfig], now: &DateTime<Tz>) {
+    let _lock = LOCK.lock().await;
+    let mut posted = load_posted().await;
+
+    // Forget digests which have been superseded, or whose window has passed.
+    posted.retain(|page| {
+        let date = page.slot.date_naive();
+        channels.iter().any(|c| {
+            c.id == page.channel
+                && c.last_announced.is_none_or(|last| last <= date)
+                && c.window
+                    .last_day(date)
+                    .is_some_and(|last| now.date_naive() <= last)
+        })
+    });
+
+    // Digests still being sent are left for the announcer to finish.
+    let (complete, mut corrected): (Vec<PostedPage>, Vec<PostedPage>) =
+        posted.into_iter().partition(|page| {
+            channels
+                .iter()
+                .any(|c| c.id == page.channel && c.last_announced == Some(page.slot.date_naive()))
+        });
+    if complete.is_empty() {
+        save_posted(&corrected).await;
+        return;
+    }
+
+    let events = match load_announcements().await {
+        Ok(events) => events,
+        Err(e) => {
+            eprintln!("Failed to load events: {e}");
+            return;
+        }
+    };
+
+    for channel in channels {
+        let pages: Vec<&PostedPage> = complete
+            .iter()
+            .filter(|p| p.channel == channel.id)
+            .collect();
+        if !pages.is_empty() {
+            corrected.extend(correct_channel(channel, pages, &events, now).await);
+        }
+    }
+    save_posted(&corrected).await;
+}
+
+/// Periodically edit posted digests to reflect changes to the events.
+pub async fn run_corrections(channels: Arc<Mutex<Vec<ChannelConfig>>>, clock: Arc<dyn Clock>) {
+    loop {
+        let registered = channels.lock().await.clone();
+        let now = clock.now();
+        correct(&registered, &now).await;
+        clock
+            .sleep_until(now + chrono::Duration::minutes(POLL_MINUTES))
+            .await;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use chrono::TimeZone;
+
+    use crate::{discord::Embed, events::Event};
+
+    use super::{cancelled, plan, Action, PostedPage};
+
+    fn page(embed: &Embed, events: &[Event]) -> PostedPage {
+        let slot = chrono_tz::UTC
+            .with_ymd_and_hms(2024, 2, 4, 9, 0, 0)
+            .unwrap();
+        PostedPage::new(
+            &"1".to_string(),
+            &slot,
+            "2".to_string(),
+            std::slice::from_ref(embed),
+            events,
+        )
+    }
+
+    #[test]
+    fn test_posted_row() {
+        let posted = page(
+            &Embed::new("Title", "Description"),
+            &[Event::new("Workshop", "06 Feb 2024")],
+        );
+        let row = posted.to_row();
+        assert_eq!(row[1], "2024-02-04T09:00:00+00:00");
+        assert_eq!(row[4..], ["Workshop", "06 Feb 2024"]);
+        assert_eq!(PostedPage::from_row(row), Some(posted));
+        assert_eq!(PostedPage::from_row(vec!["1".to_string()]), None);
+    }
+
+    #[test]
+    fn test_plan() {
+        let events = [
+            Event::new("Workshop", "06 Feb 2024"),
+            Event::new("Social", "06 Feb 2024"),
+        ];
+        let first = Embed::new("Events this Week", "");
+        let second = Embed::new("Events this Week (continued)", "");
+        let posted = [page(&first, &events[..1]), page(&second, &events[1..])];
+        let posted: Vec<&PostedPage> = posted.iter().collect();
+
+        let edited = Embed::new("Events this Week", "Edited");
+        assert_eq!(
+            plan(
+                &posted,
+                &[
+                    (vec![first.clone()], &events[..1]),
+                    (vec![edited], &events[1..])
+                ]
+            ),
+            vec![Action::Keep, Action::Edit]
+        );
+        assert_eq!(
+            plan(&posted, &[(vec![first.clone()], &events[..])]),
+            vec![Action::Keep, Action::Delete]
+        );
+        assert_eq!(
+            plan(
+                &posted[..1],
+                &[(vec![first], &events[..1]), (vec![second], &events[1..])]
+            ),
+            vec![Action::Keep, Action::Post]
+        );
+    }
+
+    #[test]
+    fn test_cancelled() {
+        let embed = Embed::new("Events this Week", "");
+        let posted = page(
+            &embed,
+            &[
+                Event::new("Workshop", "06 Feb 2024"),
+                Event::new("Social", "06 Feb 2024"),
+                Event::new("Talk", "05 Feb 2024"),
+            ],
+        );
+        let now = chrono_tz::UTC
+            .with_ymd_and_hms(2024, 2, 5, 12, 0, 0)
+            .unwrap();
+        let workshop = ("Workshop".to_string(), "06 Feb 2024".to_string());
+
+        // Next week's workshop doesn't stand in for this week's, and the
+        // talk's row going once it's over isn't a cancellation.
+        let events = [
+            Event::new("Workshop", "13 Feb 2024"),
+            Event::new("Social", "06 Feb 2024"),
+        ];
+        assert_eq!(
+            cancelled(&[&posted], &events, &[&events[1]], &now),
+            vec![(&workshop, None)]
+        );
+
+        // A workshop listed on another day this week has moved there.
+        let events = [
+            Event::new("Workshop", "08 Feb 2024"),
+            Event::new("Social", "06 Feb 2024"),
+        ];
+        let listed: Vec<&Event> = events.iter().collect();
+        assert_eq!(
+            cancelled(&[&posted], &events, &listed, &now),
+            vec![(&workshop, Some(&events[0]))]
+        );
+    }
+}
@@ -4,7 +4,7 @@ use crate::msg;
 
 use super::Result;
 
-type Csv = Vec<Vec<String>>;
+pub type Csv = Vec<Vec<String>>;
 
 enum ParseState {
     Column,
@@ -76,10 +76,13 @@ pub fn parse_csv(input: &str) -> Result<Csv> {
 }
 
 fn format_col(col: &str) -> String {
-    // Escape quotes.
-    let text = col.replace('"', "\\\"");
+    // Escape backslashes, quotes and newlines as the parser expects.
+    let text = col
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n");
     if text.contains(',') {
-        format!("\"{}\"", col)
+        format!("\"{}\"", text)
     } else {
         text
     }
@@ -154,4 +157,14 @@ mod test {
         assert_eq!(format_csv(&csv), "a,b\nc,d");
         assert_eq!(csv, parse_csv(&format_csv(&csv)).unwrap());
     }
+
+    #[test]
+    fn test_format_escapes() {
+        let csv = vec![vec![
+            "a, \"b\"".to_string(),
+            "c\nd".to_string(),
+            "e\\f".to_string(),
+        ]];
+        assert_eq!(csv, parse_csv(&format_csv(&csv)).unwrap());
+    }
 }
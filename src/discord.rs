@@ -1,21 +1,89 @@
-use std::collections::HashMap;
+use std::{collections::HashMap, future::Future};
 
 use serde::{Deserialize, Serialize};
 
+use crate::req;
+
 pub type Snowflake = String;
 
 type Timestamp = String;
 
 #[derive(Debug, Deserialize)]
-struct Channel {}
+pub struct Channel {
+    id: Snowflake,
+}
 
-#[derive(Debug, Deserialize)]
-struct User {}
+impl Channel {
+    pub fn id(&self) -> &Snowflake {
+        &self.id
+    }
+}
 
+#[derive(Clone, Debug, Deserialize)]
+#[allow(dead_code)]
+pub struct User {
+    pub id: Snowflake,
+    username: String,
+    discriminator: Option<String>,
+    global_name: Option<String>,
+    avatar: Option<String>,
+    bot: Option<bool>,
+}
+
+/// Guild member, as included in interactions.
 #[derive(Debug, Deserialize)]
-struct Member {}
+#[allow(dead_code)]
+pub struct Member {
+    user: Option<User>,
+    nick: Option<String>,
+    roles: Vec<Snowflake>,
+    joined_at: Option<Timestamp>,
+
+    /// Total permissions of the member in the channel, including overwrites.
+    permissions: Option<Permissions>,
+}
+
+impl Member {
+    pub fn roles(&self) -> &[Snowflake] {
+        &self.roles
+    }
+
+    pub fn permissions(&self) -> Permissions {
+        self.permissions.unwrap_or_default()
+    }
+}
+
+/// Bit field of permissions, sent by Discord as a string.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Permissions(u64);
+
+impl Permissions {
+    pub const ADMINISTRATOR: Self = Self(1 << 3);
+    pub const MANAGE_CHANNELS: Self = Self(1 << 4);
+
+    pub const fn contains(&self, other: Self) -> bool {
+        self.0 & other.0 == other.0
+    }
+}
+
+impl std::fmt::Display for Permissions {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl<'de> Deserialize<'de> for Permissions {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        let text = String::deserialize(deserializer)?;
+        text.parse().map(Self).map_err(serde::de::Error::custom)
+    }
+}
 
 #[derive(Debug, Deserialize)]
+#[allow(dead_code)]
 struct Role {}
 
 #[derive(Debug, Deserialize)]
@@ -28,6 +96,7 @@ struct Reaction {}
 struct MessageActivity {}
 
 #[derive(Debug, Deserialize)]
+#[allow(dead_code)]
 pub struct Message {
     id: Snowflake,
     channel_id: Snowflake,
@@ -38,7 +107,7 @@ pub struct Message {
     tts: bool,
     mention_everyone: bool,
     mentions: Vec<User>,
-    mention_roles: Vec<Role>,
+    mention_roles: Vec<Snowflake>,
     mention_channels: Option<Vec<ChannelMention>>,
     attachments: Vec<Attachment>,
     embeds: Vec<Embed>,
@@ -53,27 +122,98 @@ pub struct Message {
     activity: Option<MessageActivity>,
     application_id: Option<Snowflake>,
     message_reference: Option<MessageReference>,
-    flags: Option<i32>,
+    flags: Option<MessageFlags>,
     referenced_message: Option<Box<Message>>,
     thread: Option<Channel>,
     components: Option<Vec<MessageComponent>>,
     position: Option<i32>,
 }
 
+impl Message {
+    pub fn id(&self) -> &Snowflake {
+        &self.id
+    }
+
+    pub fn embeds(&self) -> &[Embed] {
+        &self.embeds
+    }
+
+    pub fn components(&self) -> &[MessageComponent] {
+        self.components.as_deref().unwrap_or_default()
+    }
+}
+
 #[derive(Debug, Deserialize)]
 struct Entitlement {}
 
+pub enum GuildScheduledEventPrivacyLevel {
+    GuildOnly,
+}
+
+impl GuildScheduledEventPrivacyLevel {
+    pub fn ordinal(&self) -> i32 {
+        match self {
+            Self::GuildOnly => 2,
+        }
+    }
+}
+
+pub enum GuildScheduledEventEntityType {
+    External,
+}
+
+impl GuildScheduledEventEntityType {
+    pub fn ordinal(&self) -> i32 {
+        match self {
+            Self::External => 3,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct GuildScheduledEventEntityMetadata {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub location: Option<String>,
+}
+
 #[derive(Debug, Deserialize)]
-struct ResolvedData {}
+pub struct GuildScheduledEvent {
+    id: Snowflake,
+}
+
+impl GuildScheduledEvent {
+    pub fn id(&self) -> &Snowflake {
+        &self.id
+    }
+}
 
 #[derive(Debug, Deserialize)]
+struct ResolvedData {}
+
+#[derive(Debug, Deserialize, Serialize)]
 enum ChannelType {}
 
-#[derive(Debug, Deserialize)]
-struct ApplicationCommandOptionChoice {}
+#[derive(Debug, Deserialize, Serialize)]
+pub struct ApplicationCommandOptionChoice {
+    name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    name_localizations: Option<Localisations>,
+    value: serde_json::Value,
+}
+
+impl ApplicationCommandOptionChoice {
+    pub fn new<S: ToString, V: Into<serde_json::Value>>(name: S, value: V) -> Self {
+        Self {
+            name: name.to_string(),
+            name_localizations: None,
+            value: value.into(),
+        }
+    }
+}
 
 #[derive(Debug)]
-enum ApplicationCommandOptionType {
+#[allow(dead_code)]
+pub enum ApplicationCommandOptionType {
     SubCommand,
     SubCommandGroup,
     String,
@@ -88,7 +228,7 @@ enum ApplicationCommandOptionType {
 }
 
 impl ApplicationCommandOptionType {
-    fn ordinal(&self) -> i32 {
+    pub fn ordinal(&self) -> i32 {
         match self {
             Self::SubCommand => 1,
             Self::SubCommandGroup => 2,
@@ -105,29 +245,98 @@ impl ApplicationCommandOptionType {
     }
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, Serialize)]
 struct Localisations(HashMap<String, String>);
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, Serialize)]
 pub struct ApplicationCommandOption {
     #[serde(rename = "type")]
     _type: i32,
 
     name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
     name_localizations: Option<Localisations>,
     description: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
     description_localizations: Option<Localisations>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     required: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     choices: Option<Vec<ApplicationCommandOptionChoice>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     options: Option<Vec<ApplicationCommandOption>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     channel_types: Option<Vec<ChannelType>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     min_value: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     max_value: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     min_length: Option<i32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     max_length: Option<i32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     autocomplete: Option<bool>,
 }
 
+impl ApplicationCommandOption {
+    pub fn new<S: ToString>(
+        option_type: ApplicationCommandOptionType,
+        name: S,
+        description: S,
+    ) -> Self {
+        Self {
+            _type: option_type.ordinal(),
+            name: name.to_string(),
+            name_localizations: None,
+            description: description.to_string(),
+            description_localizations: None,
+            required: None,
+            choices: None,
+            options: None,
+            channel_types: None,
+            min_value: None,
+            max_value: None,
+            min_length: None,
+            max_length: None,
+            autocomplete: None,
+        }
+    }
+
+    pub fn required(mut self, required: bool) -> Self {
+        self.required = Some(required);
+        self
+    }
+
+    /// Suggest values as the user types, rather than offering fixed choices.
+    pub fn autocomplete(mut self, autocomplete: bool) -> Self {
+        self.autocomplete = Some(autocomplete);
+        self
+    }
+
+    pub fn min_value(mut self, min: f64) -> Self {
+        self.min_value = Some(min);
+        self
+    }
+
+    pub fn max_value(mut self, max: f64) -> Self {
+        self.max_value = Some(max);
+        self
+    }
+
+    pub fn choice<S: ToString, V: Into<serde_json::Value>>(mut self, name: S, value: V) -> Self {
+        let choice = ApplicationCommandOptionChoice::new(name, value);
+
+        if let Some(choices) = &mut self.choices {
+            choices.push(choice);
+        } else {
+            self.choices = Some(vec![choice]);
+        }
+        self
+    }
+}
+
+#[allow(dead_code)]
 pub enum ApplicationCommandType {
     ChatInput,
     User,
@@ -145,6 +354,7 @@ impl ApplicationCommandType {
 }
 
 #[derive(Debug, Deserialize)]
+#[allow(dead_code)]
 pub struct ApplicationCommand {
     #[serde(rename = "type")]
     _type: Option<i32>,
@@ -165,18 +375,35 @@ pub struct ApplicationCommand {
 }
 
 #[derive(Debug, Deserialize)]
-struct ApplicationCommandInteractionDataOption {}
-
-#[derive(Debug, Deserialize)]
-struct InteractionData {
+struct ApplicationCommandInteractionDataOption {
     #[serde(rename = "type")]
     _type: i32,
 
-    id: Snowflake,
     name: String,
+    value: Option<serde_json::Value>,
+    options: Option<Vec<ApplicationCommandInteractionDataOption>>,
+    focused: Option<bool>,
+}
+
+#[derive(Debug, Deserialize)]
+#[allow(dead_code)]
+struct InteractionData {
+    // Application command data.
+    #[serde(rename = "type")]
+    _type: Option<i32>,
+    id: Option<Snowflake>,
+    name: Option<String>,
     resolved: Option<ResolvedData>,
     options: Option<Vec<ApplicationCommandInteractionDataOption>>,
     focused: Option<bool>,
+
+    // Message component data.
+    custom_id: Option<String>,
+    component_type: Option<i32>,
+    values: Option<Vec<String>>,
+
+    // Modal submit data.
+    components: Option<Vec<MessageComponent>>,
 }
 
 #[derive(Debug)]
@@ -190,6 +417,7 @@ pub enum InteractionType {
 }
 
 #[derive(Debug, Deserialize)]
+#[allow(dead_code)]
 pub struct Interaction {
     #[serde(rename = "type")]
     _type: i32,
@@ -223,16 +451,177 @@ impl Interaction {
         }
     }
 
+    /// Handle for following up on this interaction after responding to it.
+    pub fn webhook(&self) -> InteractionWebhook {
+        InteractionWebhook {
+            application_id: self.application_id.clone(),
+            token: self.token.clone(),
+        }
+    }
+
+    /// Respond with a loading state and spawn a task to replace it with the
+    /// message produced by `reply`, for handlers which may take longer than
+    /// Discord's three second limit.
+    pub fn defer<F>(&self, flags: MessageFlags, reply: F) -> InteractionResponse
+    where
+        F: Future<Output = WebhookMessage> + Send + 'static,
+    {
+        self.spawn_edit(reply);
+        InteractionResponse::deferred(flags)
+    }
+
+    fn spawn_edit<F>(&self, message: F)
+    where
+        F: Future<Output = WebhookMessage> + Send + 'static,
+    {
+        let webhook = self.webhook();
+        tokio::spawn(async move {
+            if let Err(e) = webhook.edit_original(&message.await).await {
+                eprintln!("Failed to edit deferred response: {e}");
+            }
+        });
+    }
+
     pub fn channel(&self) -> Option<&Snowflake> {
         self.channel_id.as_ref()
     }
 
+    /// Member who invoked the interaction, if in a guild.
+    pub fn member(&self) -> Option<&Member> {
+        self.member.as_ref()
+    }
+
+    /// User who invoked the interaction, in a guild or otherwise.
+    pub fn user(&self) -> Option<&User> {
+        self.member
+            .as_ref()
+            .and_then(|member| member.user.as_ref())
+            .or(self.user.as_ref())
+    }
+
     pub fn command(&self) -> Option<&str> {
-        self.data.as_ref().map(|data| data.name.as_str())
+        self.data.as_ref().and_then(|data| data.name.as_deref())
+    }
+
+    /// Custom id of the component which was used.
+    pub fn custom_id(&self) -> Option<&str> {
+        self.data
+            .as_ref()
+            .and_then(|data| data.custom_id.as_deref())
+    }
+
+    /// Value entered in a text input of a submitted modal.
+    pub fn text_value(&self, custom_id: &str) -> Option<&str> {
+        self.data
+            .as_ref()
+            .and_then(|data| data.components.as_ref())?
+            .iter()
+            .flat_map(|row| row.components())
+            .find(|input| input.custom_id() == Some(custom_id))
+            .and_then(|input| input.value.as_deref())
+    }
+
+    /// Message a component interaction was triggered from.
+    pub fn message(&self) -> Option<&Message> {
+        self.message.as_ref()
+    }
+
+    /// Name of the subcommand which was invoked.
+    pub fn subcommand(&self) -> Option<&str> {
+        self.data
+            .as_ref()
+            .and_then(|data| data.options.as_ref())?
+            .iter()
+            .find(|option| option._type == ApplicationCommandOptionType::SubCommand.ordinal())
+            .map(|option| option.name.as_str())
+    }
+
+    /// Name and current value of the option being autocompleted.
+    pub fn focused_option(&self) -> Option<(&str, &serde_json::Value)> {
+        self.data
+            .as_ref()
+            .and_then(|data| data.options.as_ref())?
+            .iter()
+            .flat_map(|option| std::iter::once(option).chain(option.options.iter().flatten()))
+            .find(|option| option.focused == Some(true))
+            .and_then(|option| Some((option.name.as_str(), option.value.as_ref()?)))
+    }
+
+    /// Value of a top level option passed to the command.
+    pub fn option(&self, name: &str) -> Option<&serde_json::Value> {
+        self.data
+            .as_ref()
+            .and_then(|data| data.options.as_ref())
+            .and_then(|options| options.iter().find(|option| option.name == name))
+            .and_then(|option| option.value.as_ref())
+    }
+
+    pub fn option_str(&self, name: &str) -> Option<&str> {
+        self.option(name).and_then(|value| value.as_str())
+    }
+
+    pub fn option_int(&self, name: &str) -> Option<i64> {
+        self.option(name).and_then(|value| value.as_i64())
+    }
+
+    pub fn option_bool(&self, name: &str) -> Option<bool> {
+        self.option(name).and_then(|value| value.as_bool())
+    }
+}
+
+/// Token and application needed to edit the response to an interaction and
+/// send follow up messages. Valid for 15 minutes after the interaction.
+#[derive(Clone, Debug)]
+pub struct InteractionWebhook {
+    application_id: Snowflake,
+    token: String,
+}
+
+impl InteractionWebhook {
+    fn uri(&self, path: &str) -> String {
+        req::api_uri(format!(
+            "/webhooks/{}/{}{path}",
+            self.application_id, self.token
+        ))
+    }
+
+    pub async fn edit_original(&self, message: &WebhookMessage) -> crate::Result<Message> {
+        req::patch(self.uri("/messages/@original"), message).await
+    }
+}
+
+/// Body of a follow up message or an edit to an interaction response. Unset
+/// fields are left unchanged when editing.
+#[derive(Debug, Default, Serialize)]
+pub struct WebhookMessage {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    content: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    embeds: Option<Vec<Embed>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    allowed_mentions: Option<AllowedMentions>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    components: Option<Vec<MessageComponent>>,
+}
+
+impl WebhookMessage {
+    pub fn content<S: ToString>(content: S) -> Self {
+        Self {
+            content: Some(content.to_string()),
+            ..Default::default()
+        }
+    }
+
+    pub fn embeds(embeds: Vec<Embed>) -> Self {
+        Self {
+            embeds: Some(embeds),
+            ..Default::default()
+        }
     }
 }
 
 #[derive(Debug)]
+#[allow(dead_code)]
 enum InteractionCallbackType {
     Pong,
     ChannelMessageWithSource,
@@ -269,65 +658,256 @@ impl Serialize for InteractionCallbackType {
 }
 
 #[derive(Clone, Debug, Deserialize, Serialize)]
-struct EmbedFooter {}
+pub struct EmbedFooter {
+    pub text: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub icon_url: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    proxy_icon_url: Option<String>,
+}
 
+/// An image or thumbnail. Only the URL is sent; the rest is filled in by
+/// Discord.
 #[derive(Clone, Debug, Deserialize, Serialize)]
-struct EmbedImage {}
+pub struct EmbedImage {
+    pub url: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    proxy_url: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    height: Option<i32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    width: Option<i32>,
+}
 
-#[derive(Clone, Debug, Deserialize, Serialize)]
-struct EmbedThumbnail {}
+impl EmbedImage {
+    fn new(url: String) -> Self {
+        Self {
+            url,
+            proxy_url: None,
+            height: None,
+            width: None,
+        }
+    }
+}
+
+pub type EmbedThumbnail = EmbedImage;
 
 #[derive(Clone, Debug, Deserialize, Serialize)]
-struct EmbedVideo {}
+pub struct EmbedVideo {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    url: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    proxy_url: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    height: Option<i32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    width: Option<i32>,
+}
 
 #[derive(Clone, Debug, Deserialize, Serialize)]
-struct EmbedProvider {}
+pub struct EmbedProvider {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    url: Option<String>,
+}
 
 #[derive(Clone, Debug, Deserialize, Serialize)]
-struct EmbedAuthor {}
+pub struct EmbedAuthor {}
 
 #[derive(Clone, Debug, Deserialize, Serialize)]
-struct EmbedField {
-    name: String,
-    value: String,
+pub struct EmbedField {
+    pub name: String,
+    pub value: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
     inline: Option<bool>,
 }
 
 #[derive(Clone, Debug, Default, Deserialize, Serialize)]
 pub struct Embed {
-    #[serde(rename = "type")]
+    #[serde(rename = "type", skip_serializing_if = "Option::is_none")]
     _type: Option<String>,
 
+    #[serde(skip_serializing_if = "Option::is_none")]
     title: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     description: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     url: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     timestamp: Option<Timestamp>,
+    #[serde(rename = "color", skip_serializing_if = "Option::is_none")]
     colour: Option<i32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     footer: Option<EmbedFooter>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     image: Option<EmbedImage>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     thumbnail: Option<EmbedThumbnail>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     video: Option<EmbedVideo>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     provider: Option<EmbedProvider>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     author: Option<EmbedAuthor>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     fields: Option<Vec<EmbedField>>,
 }
 
+/// Shorten text to at most `max` characters, marking where it was cut.
+pub fn truncate(text: String, max: usize) -> String {
+    if text.chars().count() <= max {
+        text
+    } else {
+        let mut text: String = text.chars().take(max.saturating_sub(1)).collect();
+        text.push('…');
+        text
+    }
+}
+
 impl Embed {
     const COLOUR: i32 = 0xC84200;
 
+    // Limits imposed by Discord, in characters.
+    pub const MAX_FIELDS: usize = 25;
+    pub const MAX_TITLE_LENGTH: usize = 256;
+    pub const MAX_DESCRIPTION_LENGTH: usize = 4096;
+    pub const MAX_FIELD_NAME_LENGTH: usize = 256;
+    pub const MAX_FIELD_VALUE_LENGTH: usize = 1024;
+    pub const MAX_FOOTER_LENGTH: usize = 2048;
+
+    /// Most characters across all embeds in a message.
+    pub const MAX_LENGTH: usize = 6000;
+
+    /// Most embeds in a message.
+    pub const MAX_EMBEDS: usize = 10;
+
+    /// Embed with the given title and description, truncated to fit. An empty
+    /// description is left out, as Discord rejects it.
     pub fn new<S: ToString>(title: S, description: S) -> Self {
+        let description = description.to_string();
         Self {
-            title: Some(title.to_string()),
-            description: Some(description.to_string()),
+            title: Some(truncate(title.to_string(), Self::MAX_TITLE_LENGTH)),
+            description: (!description.is_empty())
+                .then(|| truncate(description, Self::MAX_DESCRIPTION_LENGTH)),
             colour: Some(Self::COLOUR),
             ..Default::default()
         }
     }
 
+    /// Characters counted towards Discord's limit on the total size.
+    pub fn length(&self) -> usize {
+        let len = |text: Option<&str>| text.map_or(0, |t| t.chars().count());
+        len(self.title.as_deref())
+            + len(self.description.as_deref())
+            + len(self.footer.as_ref().map(|f| f.text.as_str()))
+            + self
+                .fields()
+                .iter()
+                .map(|f| f.name.chars().count() + f.value.chars().count())
+                .sum::<usize>()
+    }
+
+    /// Characters a field would add towards the limit on the total size, once
+    /// truncated to fit.
+    pub fn field_length(name: &str, value: &str) -> usize {
+        name.chars().count().min(Self::MAX_FIELD_NAME_LENGTH)
+            + value.chars().count().min(Self::MAX_FIELD_VALUE_LENGTH)
+    }
+
+    /// Whether a field could be added without exceeding the limits on the
+    /// number of fields or the total size.
+    pub fn has_room(&self, name: &str, value: &str) -> bool {
+        self.fields().len() < Self::MAX_FIELDS
+            && self.length() + Self::field_length(name, value) <= Self::MAX_LENGTH
+    }
+
+    /// Whether the embed is within all of Discord's limits.
+    #[cfg(test)]
+    pub fn is_valid(&self) -> bool {
+        let within = |text: Option<&str>, max| text.is_none_or(|t| t.chars().count() <= max);
+        within(self.title.as_deref(), Self::MAX_TITLE_LENGTH)
+            && within(self.description.as_deref(), Self::MAX_DESCRIPTION_LENGTH)
+            && within(
+                self.footer.as_ref().map(|f| f.text.as_str()),
+                Self::MAX_FOOTER_LENGTH,
+            )
+            && self.fields().len() <= Self::MAX_FIELDS
+            && self.fields().iter().all(|f| {
+                f.name.chars().count() <= Self::MAX_FIELD_NAME_LENGTH
+                    && f.value.chars().count() <= Self::MAX_FIELD_VALUE_LENGTH
+            })
+            && self.length() <= Self::MAX_LENGTH
+    }
+
+    /// The leading embeds which fit in a single message.
+    pub fn fit_message(embeds: Vec<Embed>) -> Vec<Embed> {
+        let mut total = 0;
+        embeds
+            .into_iter()
+            .take(Self::MAX_EMBEDS)
+            .take_while(|embed| {
+                total += embed.length();
+                total <= Self::MAX_LENGTH
+            })
+            .collect()
+    }
+
+    /// Link the title to a URL.
+    pub fn url<S: ToString>(mut self, url: S) -> Self {
+        self.url = Some(url.to_string());
+        self
+    }
+
+    pub fn colour(mut self, colour: i32) -> Self {
+        self.colour = Some(colour);
+        self
+    }
+
+    pub fn footer<S: ToString>(mut self, text: S) -> Self {
+        self.footer = Some(EmbedFooter {
+            text: truncate(text.to_string(), Self::MAX_FOOTER_LENGTH),
+            icon_url: None,
+            proxy_icon_url: None,
+        });
+        self
+    }
+
+    pub fn thumbnail<S: ToString>(mut self, url: S) -> Self {
+        self.thumbnail = Some(EmbedImage::new(url.to_string()));
+        self
+    }
+
+    #[cfg(test)]
+    pub fn title(&self) -> Option<&str> {
+        self.title.as_deref()
+    }
+
+    #[cfg(test)]
+    pub fn footer_text(&self) -> Option<&str> {
+        self.footer.as_ref().map(|footer| footer.text.as_str())
+    }
+
+    #[cfg(test)]
+    pub fn description(&self) -> Option<&str> {
+        self.description.as_deref()
+    }
+
+    pub fn fields(&self) -> &[EmbedField] {
+        self.fields.as_deref().unwrap_or_default()
+    }
+
+    pub fn fields_mut(&mut self) -> &mut [EmbedField] {
+        self.fields.as_deref_mut().unwrap_or_default()
+    }
+
+    /// Add a field, truncating its name and value to fit. Callers should
+    /// check `has_room` first, as the field count and total size aren't
+    /// enforced here.
     pub fn add_field(&mut self, name: String, value: String) {
         let field = EmbedField {
-            name,
-            value,
+            name: truncate(name, Self::MAX_FIELD_NAME_LENGTH),
+            value: truncate(value, Self::MAX_FIELD_VALUE_LENGTH),
             inline: None,
         };
 
@@ -339,38 +919,290 @@ impl Embed {
     }
 }
 
-#[derive(Debug, Serialize)]
-pub struct AllowedMentions {}
+#[derive(Debug, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AllowedMentionType {
+    /// Both @everyone and @here.
+    Everyone,
+}
+
+/// Which mentions in a message's content may ping. Mentions not allowed are
+/// still shown but notify no one.
+#[derive(Debug, Default, PartialEq, Serialize)]
+pub struct AllowedMentions {
+    parse: Vec<AllowedMentionType>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    roles: Vec<Snowflake>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    users: Vec<Snowflake>,
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    replied_user: bool,
+}
+
+impl AllowedMentions {
+    pub fn none() -> Self {
+        Self::default()
+    }
+
+    pub fn everyone() -> Self {
+        Self {
+            parse: vec![AllowedMentionType::Everyone],
+            ..Default::default()
+        }
+    }
+
+    pub fn role(role: Snowflake) -> Self {
+        Self {
+            roles: vec![role],
+            ..Default::default()
+        }
+    }
+}
 
 #[derive(Debug, Deserialize, Serialize)]
 pub struct MessageReference {}
 
-#[derive(Debug, Deserialize, Serialize)]
-pub struct MessageComponent {}
+pub enum ComponentType {
+    ActionRow,
+    Button,
+    TextInput,
+}
+
+impl ComponentType {
+    pub fn ordinal(&self) -> i32 {
+        match self {
+            Self::ActionRow => 1,
+            Self::Button => 2,
+            Self::TextInput => 4,
+        }
+    }
+}
+
+pub enum ButtonStyle {
+    Primary,
+    Secondary,
+    Success,
+    Danger,
+}
+
+impl ButtonStyle {
+    pub fn ordinal(&self) -> i32 {
+        match self {
+            Self::Primary => 1,
+            Self::Secondary => 2,
+            Self::Success => 3,
+            Self::Danger => 4,
+        }
+    }
+}
+
+pub enum TextInputStyle {
+    Short,
+    Paragraph,
+}
+
+impl TextInputStyle {
+    pub fn ordinal(&self) -> i32 {
+        match self {
+            Self::Short => 1,
+            Self::Paragraph => 2,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct PartialEmoji {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    id: Option<Snowflake>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    animated: Option<bool>,
+}
+
+/// Interactive component of a message. Discord distinguishes components by
+/// their type, with fields depending on the type, so they are modelled as one
+/// struct with constructors for each type.
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub struct MessageComponent {
+    #[serde(rename = "type")]
+    _type: i32,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    custom_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    components: Option<Vec<MessageComponent>>,
+
+    // Buttons.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    style: Option<i32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    label: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    emoji: Option<PartialEmoji>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    url: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    disabled: Option<bool>,
+
+    // Text inputs.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    placeholder: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    min_length: Option<i32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    max_length: Option<i32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    required: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    value: Option<String>,
+}
+
+impl MessageComponent {
+    /// Most action rows allowed in a message.
+    pub const MAX_ROWS: usize = 5;
+
+    fn new(component_type: ComponentType) -> Self {
+        Self {
+            _type: component_type.ordinal(),
+            ..Default::default()
+        }
+    }
+
+    fn with_custom_id<S: ToString>(component_type: ComponentType, custom_id: S) -> Self {
+        Self {
+            custom_id: Some(custom_id.to_string()),
+            ..Self::new(component_type)
+        }
+    }
+
+    pub fn action_row(components: Vec<MessageComponent>) -> Self {
+        Self {
+            components: Some(components),
+            ..Self::new(ComponentType::ActionRow)
+        }
+    }
+
+    pub fn button<S: ToString>(style: ButtonStyle, label: S, custom_id: S) -> Self {
+        Self {
+            style: Some(style.ordinal()),
+            label: Some(label.to_string()),
+            ..Self::with_custom_id(ComponentType::Button, custom_id)
+        }
+    }
+
+    pub fn text_input<S: ToString>(style: TextInputStyle, custom_id: S, label: S) -> Self {
+        Self {
+            style: Some(style.ordinal()),
+            label: Some(label.to_string()),
+            ..Self::with_custom_id(ComponentType::TextInput, custom_id)
+        }
+    }
+
+    pub fn disabled(mut self, disabled: bool) -> Self {
+        self.disabled = Some(disabled);
+        self
+    }
+
+    pub fn placeholder<S: ToString>(mut self, placeholder: S) -> Self {
+        self.placeholder = Some(placeholder.to_string());
+        self
+    }
+
+    pub fn length(mut self, min: i32, max: i32) -> Self {
+        self.min_length = Some(min);
+        self.max_length = Some(max);
+        self
+    }
+
+    pub fn required(mut self, required: bool) -> Self {
+        self.required = Some(required);
+        self
+    }
+
+    /// Prefill a text input.
+    pub fn value<S: ToString>(mut self, value: S) -> Self {
+        self.value = Some(value.to_string());
+        self
+    }
+
+    pub fn custom_id(&self) -> Option<&str> {
+        self.custom_id.as_deref()
+    }
+
+    /// Components within an action row.
+    pub fn components(&self) -> &[MessageComponent] {
+        self.components.as_deref().unwrap_or_default()
+    }
+}
 
 #[derive(Debug, Deserialize, Serialize)]
 pub struct Attachment {}
 
+/// Bit field of message flags.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(transparent)]
+pub struct MessageFlags(i32);
+
+impl MessageFlags {
+    /// Only visible to the user who invoked the interaction.
+    pub const EPHEMERAL: Self = Self(1 << 6);
+
+    pub const fn empty() -> Self {
+        Self(0)
+    }
+}
+
+impl std::ops::BitOr for MessageFlags {
+    type Output = Self;
+
+    fn bitor(self, rhs: Self) -> Self {
+        Self(self.0 | rhs.0)
+    }
+}
+
+impl std::ops::BitOrAssign for MessageFlags {
+    fn bitor_assign(&mut self, rhs: Self) {
+        self.0 |= rhs.0;
+    }
+}
+
 #[derive(Debug, Serialize)]
 #[serde(untagged)]
 enum InteractionCallbackData {
+    // Unset fields are omitted so that updates leave them unchanged.
     Message {
+        #[serde(skip_serializing_if = "Option::is_none")]
         tts: Option<bool>,
+        #[serde(skip_serializing_if = "Option::is_none")]
         content: Option<String>,
+        #[serde(skip_serializing_if = "Option::is_none")]
         embeds: Option<Vec<Embed>>,
+        #[serde(skip_serializing_if = "Option::is_none")]
         allowed_mentions: Option<AllowedMentions>,
-        flags: Option<i32>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        flags: Option<MessageFlags>,
+        #[serde(skip_serializing_if = "Option::is_none")]
         components: Option<Vec<MessageComponent>>,
+        #[serde(skip_serializing_if = "Option::is_none")]
         attachments: Option<Vec<Attachment>>,
     },
+    Modal {
+        custom_id: String,
+        title: String,
+        components: Vec<MessageComponent>,
+    },
+    Autocomplete {
+        choices: Vec<ApplicationCommandOptionChoice>,
+    },
 }
 
 impl InteractionCallbackData {
-    fn message(content: String) -> Self {
+    fn message(content: Option<String>, embeds: Option<Vec<Embed>>) -> Self {
         Self::Message {
             tts: Some(false),
-            content: Some(content),
-            embeds: None,
+            content,
+            embeds,
             allowed_mentions: None,
             flags: None,
             components: None,
@@ -401,13 +1233,185 @@ impl InteractionResponse {
     pub fn message<S: ToString>(content: S) -> Self {
         Self::new(
             InteractionCallbackType::ChannelMessageWithSource,
-            Some(InteractionCallbackData::message(content.to_string())),
+            Some(InteractionCallbackData::message(
+                Some(content.to_string()),
+                None,
+            )),
+        )
+    }
+
+    pub fn embeds(embeds: Vec<Embed>) -> Self {
+        Self::new(
+            InteractionCallbackType::ChannelMessageWithSource,
+            Some(InteractionCallbackData::message(None, Some(embeds))),
+        )
+    }
+
+    /// Acknowledge the interaction, showing a loading state until the
+    /// original response is edited. Flags apply to the eventual message.
+    pub fn deferred(flags: MessageFlags) -> Self {
+        Self::new(
+            InteractionCallbackType::DeferredChannelMessageWithSource,
+            Some(InteractionCallbackData::message(None, None)),
+        )
+        .flags(flags)
+    }
+
+    /// Add flags to the message sent in response.
+    pub fn flags(mut self, flags: MessageFlags) -> Self {
+        if let Some(InteractionCallbackData::Message { flags: current, .. }) = &mut self.data {
+            *current = Some(current.unwrap_or_default() | flags);
+        }
+        self
+    }
+
+    /// Only show the response to the user who invoked the interaction.
+    pub fn ephemeral(self) -> Self {
+        self.flags(MessageFlags::EPHEMERAL)
+    }
+
+    /// Respond to a component interaction by editing the message the
+    /// component is attached to.
+    pub fn update(embeds: Vec<Embed>, components: Vec<MessageComponent>) -> Self {
+        Self::new(
+            InteractionCallbackType::UpdateMessage,
+            Some(InteractionCallbackData::Message {
+                tts: None,
+                content: None,
+                embeds: Some(embeds),
+                allowed_mentions: None,
+                flags: None,
+                components: Some(components),
+                attachments: None,
+            }),
+        )
+    }
+
+    /// Suggestions for the option being autocompleted.
+    pub fn autocomplete(choices: Vec<ApplicationCommandOptionChoice>) -> Self {
+        Self::new(
+            InteractionCallbackType::ApplicationCommandAutocompleteResult,
+            Some(InteractionCallbackData::Autocomplete { choices }),
+        )
+    }
+
+    /// Open a form for the user to fill in, with each text input in its own
+    /// action row.
+    pub fn modal<S: ToString>(custom_id: S, title: S, inputs: Vec<MessageComponent>) -> Self {
+        Self::new(
+            InteractionCallbackType::Modal,
+            Some(InteractionCallbackData::Modal {
+                custom_id: custom_id.to_string(),
+                title: title.to_string(),
+                components: inputs
+                    .into_iter()
+                    .map(|input| MessageComponent::action_row(vec![input]))
+                    .collect(),
+            }),
         )
     }
 }
 
 #[derive(Debug, Deserialize)]
+#[allow(dead_code)]
 pub struct ErrorResponse {
     pub message: String,
     code: i32,
 }
+
+#[cfg(test)]
+mod test {
+    use super::{Embed, InteractionResponse, MessageFlags, WebhookMessage};
+
+    #[test]
+    fn test_message_flags() {
+        let flags = MessageFlags::empty() | MessageFlags::EPHEMERAL;
+        assert_eq!(flags, MessageFlags::EPHEMERAL);
+        assert_eq!(serde_json::to_string(&flags).unwrap(), "64");
+    }
+
+    #[test]
+    fn test_ephemeral_response() {
+        let resp = serde_json::to_value(InteractionResponse::message("Hi").ephemeral()).unwrap();
+        assert_eq!(resp["type"], 4);
+        assert_eq!(resp["data"]["content"], "Hi");
+        assert_eq!(resp["data"]["flags"], 64);
+
+        let resp = serde_json::to_value(InteractionResponse::message("Hi")).unwrap();
+        assert_eq!(resp["data"]["flags"], serde_json::Value::Null);
+    }
+
+    #[test]
+    fn test_webhook_message_omits_unset() {
+        let message = WebhookMessage::content("Done");
+        assert_eq!(
+            serde_json::to_string(&message).unwrap(),
+            r#"{"content":"Done"}"#
+        );
+    }
+
+    #[test]
+    fn test_deferred_responses() {
+        assert_eq!(
+            serde_json::to_value(InteractionResponse::deferred(MessageFlags::EPHEMERAL)).unwrap()
+                ["data"]["flags"],
+            64
+        );
+    }
+
+    #[test]
+    fn test_embed_serialisation() {
+        let mut embed = Embed::new("Title", "Description")
+            .url("https://example.com")
+            .footer("Footer")
+            .thumbnail("https://example.com/logo.png");
+        embed.add_field("Name".to_string(), "Value".to_string());
+        assert_eq!(
+            serde_json::to_value(&embed).unwrap(),
+            serde_json::json!({
+                "title": "Title",
+                "description": "Description",
+                "url": "https://example.com",
+                "color": 0xC84200,
+                "footer": { "text": "Footer" },
+                "thumbnail": { "url": "https://example.com/logo.png" },
+                "fields": [{ "name": "Name", "value": "Value" }],
+            })
+        );
+
+        let received: Embed = serde_json::from_value(serde_json::json!({
+            "type": "rich",
+            "title": "Title",
+            "color": 0xC84200,
+            "footer": { "text": "Footer", "proxy_icon_url": "https://cdn" },
+        }))
+        .unwrap();
+        assert_eq!(received.colour, Some(Embed::COLOUR));
+        assert_eq!(received.footer_text(), Some("Footer"));
+    }
+
+    #[test]
+    fn test_embed_limits() {
+        let mut embed = Embed::new("x".repeat(300), String::new());
+        assert_eq!(
+            embed.title().unwrap().chars().count(),
+            Embed::MAX_TITLE_LENGTH
+        );
+        assert!(embed.title().unwrap().ends_with('…'));
+
+        embed.add_field("Name".to_string(), "y".repeat(2000));
+        assert_eq!(embed.fields()[0].value.chars().count(), 1024);
+        assert!(embed.is_valid());
+
+        while embed.has_room("Name", "Value") {
+            embed.add_field("Name".to_string(), "Value".to_string());
+        }
+        assert_eq!(embed.fields().len(), Embed::MAX_FIELDS);
+        assert!(embed.is_valid());
+
+        let mut big = Embed::new(String::new(), "z".repeat(4000));
+        big.add_field("Name".to_string(), "v".repeat(1024));
+        assert!(!big.has_room("Name", &"v".repeat(2000)));
+        assert_eq!(Embed::fit_message(vec![big.clone(), big]).len(), 1);
+    }
+}
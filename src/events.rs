@@ -0,0 +1,411 @@
+use std::{collections::HashSet, future::Future};
+
+use chrono::DateTime;
+use chrono_tz::Tz;
+use tokio::sync::Mutex;
+
+use crate::{
+    consts::{EVENTS_FILE, EVENTS_SHEET_CSV},
+    csv::{load_csv, parse_csv, write_csv, Csv},
+    err, msg, req, Result,
+};
+
+/// Rows at the top of the sheet before the first event.
+const HEADER_ROWS: usize = 2;
+
+const ATTENDING_COL: usize = 4;
+
+/// Held while rewriting the events file.
+static FILE_LOCK: Mutex<()> = Mutex::const_new(());
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Event {
+    pub name: String,
+    pub date: String,
+    pub location: String,
+    pub category: Option<String>,
+    pub attending: Option<String>,
+    pub notes: Option<String>,
+
+    /// Page with more details about the event.
+    pub link: Option<String>,
+}
+
+impl Event {
+    /// Event from a row of the sheet. Empty optional cells are treated as
+    /// missing.
+    pub fn from_row(row: &[String]) -> Option<Self> {
+        let optional = |col: usize| row.get(col).filter(|cell| !cell.is_empty()).cloned();
+        Some(Self {
+            name: row.first()?.clone(),
+            date: row.get(1)?.clone(),
+            location: row.get(2)?.clone(),
+            category: optional(3),
+            attending: optional(ATTENDING_COL),
+            notes: optional(5),
+            link: optional(6),
+        })
+    }
+
+    pub fn to_row(&self) -> Vec<String> {
+        vec![
+            self.name.clone(),
+            self.date.clone(),
+            self.location.clone(),
+            self.category.clone().unwrap_or_default(),
+            self.attending.clone().unwrap_or_default(),
+            self.notes.clone().unwrap_or_default(),
+            self.link.clone().unwrap_or_default(),
+        ]
+    }
+
+    pub fn start_time(&self, tz: &Tz) -> Option<DateTime<Tz>> {
+        start_time(&self.date, tz)
+    }
+
+    /// Whether the event goes by this name, ignoring case and surrounding
+    /// whitespace.
+    pub fn has_name(&self, name: &str) -> bool {
+        self.name.trim().to_lowercase() == name.trim().to_lowercase()
+    }
+
+    /// When the event starts, as Discord timestamp markup so that each reader
+    /// sees it in their own timezone along with a countdown. Falls back to the
+    /// sheet's text if the date can't be read.
+    ///
+    /// The sheet only has dates, so only the date is shown, and for midday
+    /// rather than the start of the day so that readers in timezones behind
+    /// this one don't see the day before.
+    pub fn date_string(&self, tz: &Tz) -> String {
+        if let Some(dt) = self.start_time(tz) {
+            let midday = (dt + chrono::Duration::hours(12)).timestamp();
+            format!("<t:{midday}:D> (<t:{}:R>)", dt.timestamp())
+        } else {
+            self.date.clone()
+        }
+    }
+
+    /// Stable identifier for the event, derived from its name and date. Short
+    /// enough to fit in component custom ids.
+    pub fn key(&self) -> String {
+        stable_hash(&format!("{}\n{}", self.name, self.date))
+    }
+
+    /// Hash of the event's details other than the attending count, which is
+    /// written back to the events file. Short enough to fit in custom ids.
+    pub fn fingerprint(&self) -> String {
+        let mut row = self.to_row();
+        row[ATTENDING_COL].clear();
+        stable_hash(&row.join("\n"))
+    }
+
+    /// One line description of when and where the event is.
+    pub fn summary(&self, tz: &Tz) -> String {
+        let date = self.date_string(tz);
+        let notes = if let Some(notes) = &self.notes {
+            format!(". {notes}")
+        } else {
+            String::new()
+        };
+        format!("{}, {}{}", date, self.location, notes)
+    }
+}
+
+#[cfg(test)]
+impl Event {
+    /// Event at the library with no optional details, for tests.
+    pub fn new(name: &str, date: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            date: date.to_string(),
+            location: "Library".to_string(),
+            category: None,
+            attending: None,
+            notes: None,
+            link: None,
+        }
+    }
+}
+
+/// Start of the day of a date in the sheet's format, e.g. `06 Feb 2024`. For
+/// a range of days, e.g. `06-07 Feb 2024`, the first day.
+pub fn start_time(date: &str, tz: &Tz) -> Option<DateTime<Tz>> {
+    let parts: Vec<&str> = date.split(' ').collect();
+    let day = parts.first();
+    let month = parts.get(1);
+    let year = parts.get(2);
+
+    let (Some(d), Some(m), Some(y)) = (day, month, year) else {
+        return None;
+    };
+
+    let day: u32 = if d.contains('-') {
+        if let Some((from, _to)) = d.split_once('-') {
+            from.parse().ok()?
+        } else {
+            return None;
+        }
+    } else {
+        d.parse().ok()?
+    };
+
+    let month: chrono::Month = m.parse().ok()?;
+    let year: i32 = y.parse().ok()?;
+
+    let naive = chrono::NaiveDate::from_ymd_opt(year, month.number_from_month(), day)?;
+    let naive = naive.and_time(chrono::NaiveTime::MIN);
+    let date = chrono::TimeZone::from_local_datetime(tz, &naive);
+    date.single()
+}
+
+/// Hex digest of the text which, unlike the standard library's hasher, is
+/// guaranteed not to change between releases. 64 bit FNV-1a.
+pub fn stable_hash(text: &str) -> String {
+    const OFFSET: u64 = 0xcbf29ce484222325;
+    const PRIME: u64 = 0x100000001b3;
+
+    let hash = text
+        .bytes()
+        .fold(OFFSET, |hash, b| (hash ^ u64::from(b)).wrapping_mul(PRIME));
+    format!("{hash:016x}")
+}
+
+/// Events in the rows of the sheet. Rows with the same name and date are the
+/// same event, and would share RSVP buttons, so only the first is kept.
+fn parse_events(data: &Csv) -> Vec<Event> {
+    let mut keys = HashSet::new();
+    data.iter()
+        .skip(HEADER_ROWS)
+        .filter_map(|row| Event::from_row(row))
+        .filter(|event| keys.insert(event.key()))
+        .collect()
+}
+
+/// Load events from the local events file if one is configured, otherwise
+/// from the published sheet.
+pub async fn load_announcements() -> Result<Vec<Event>> {
+    let data = match EVENTS_FILE {
+        Some(file) => load_csv(file).await?,
+        None => parse_csv(&req::get(EVENTS_SHEET_CSV).await?)?,
+    };
+    let mut events = parse_events(&data);
+
+    // TODO remove once satisfied with testing.
+    events.push(Event {
+        name: "Test Event 1".to_string(),
+        date: "02 Feb 2024".to_string(),
+        location: "Location 1".to_string(),
+        category: None,
+        attending: None,
+        notes: Some("Notes for event".to_string()),
+        link: None,
+    });
+    events.push(Event {
+        name: "Test Event 2".to_string(),
+        date: "03 Feb 2024".to_string(),
+        location: "Location 2".to_string(),
+        category: None,
+        attending: None,
+        notes: Some("Notes for event".to_string()),
+        link: None,
+    });
+
+    Ok(events)
+}
+
+/// Whether events can be written back to their source.
+pub fn writable() -> bool {
+    EVENTS_FILE.is_some()
+}
+
+/// Set the attending column of the row for the event with the given key,
+/// failing if the row no longer has the given fingerprint.
+fn set_attending(data: &mut Csv, key: &str, fingerprint: &str, attending: String) -> Result<()> {
+    let Some((row, event)) = data
+        .iter_mut()
+        .skip(HEADER_ROWS)
+        .filter_map(|row| Event::from_row(row).map(|event| (row, event)))
+        .find(|(_, event)| event.key() == key)
+    else {
+        return err("The event is no longer in the events file.");
+    };
+
+    if event.fingerprint() != fingerprint {
+        return err(format!("{} was edited since it was announced.", event.name));
+    }
+
+    if row.len() <= ATTENDING_COL {
+        row.resize(ATTENDING_COL + 1, String::new());
+    }
+    row[ATTENDING_COL] = attending;
+    Ok(())
+}
+
+/// Add a row for an event, failing if the event is already present.
+fn add_event(data: &mut Csv, event: &Event) -> Result<()> {
+    let key = event.key();
+    if data
+        .iter()
+        .skip(HEADER_ROWS)
+        .any(|row| Event::from_row(row).is_some_and(|e| e.key() == key))
+    {
+        return err(format!(
+            "{} on {} has already been added.",
+            event.name, event.date
+        ));
+    }
+
+    // Keep the header rows in place even if the file is new.
+    if data.len() < HEADER_ROWS {
+        data.resize(HEADER_ROWS, vec![String::new()]);
+    }
+    data.push(event.to_row());
+    Ok(())
+}
+
+/// Append an event to the events file.
+pub async fn append_event(event: &Event) -> Result<()> {
+    let file = EVENTS_FILE.ok_or_else(|| msg("Events source is not writable."))?;
+
+    let _lock = FILE_LOCK.lock().await;
+    let mut data = load_csv(file).await.unwrap_or_default();
+    add_event(&mut data, event)?;
+    write_csv(&data, file).await
+}
+
+/// Write the attending column for the event with the given key back to the
+/// events file, unless its row has been edited since it was announced with
+/// `fingerprint`. The value is only worked out once the file is locked, so
+/// that concurrent updates are written in order.
+pub async fn update_attending<F: Future<Output = String>>(
+    key: &str,
+    fingerprint: &str,
+    attending: F,
+) -> Result<()> {
+    let file = EVENTS_FILE.ok_or_else(|| msg("Events source is not writable."))?;
+
+    let _lock = FILE_LOCK.lock().await;
+    let mut data = load_csv(file).await?;
+    set_attending(&mut data, key, fingerprint, attending.await)?;
+    write_csv(&data, file).await
+}
+
+#[cfg(test)]
+mod test {
+    use super::{add_event, parse_events, set_attending, Event};
+
+    fn sheet() -> Vec<Vec<String>> {
+        [
+            vec!["Events"],
+            vec!["Name", "Date", "Location", "Category", "Attending", "Notes"],
+            vec![
+                "Workshop",
+                "06 Feb 2024",
+                "Library",
+                "Workshops",
+                "",
+                "Bring a pen",
+            ],
+            vec!["Social", "07 Feb 2024", "The Pub"],
+        ]
+        .into_iter()
+        .map(|row| row.into_iter().map(str::to_string).collect())
+        .collect()
+    }
+
+    #[test]
+    fn test_parse_events() {
+        let mut data = sheet();
+        data.push(data[2].clone());
+        let events = parse_events(&data);
+        let names: Vec<&str> = events.iter().map(|e| e.name.as_str()).collect();
+        assert_eq!(names, vec!["Workshop", "Social"]);
+    }
+
+    #[test]
+    fn test_set_attending() {
+        let mut data = sheet();
+        let workshop = Event::from_row(&data[2]).unwrap();
+        let social = Event::from_row(&data[3]).unwrap();
+
+        let (key, fingerprint) = (workshop.key(), workshop.fingerprint());
+        set_attending(&mut data, &key, &fingerprint, "3".to_string()).unwrap();
+        assert_eq!(data[2][4], "3");
+        assert_eq!(data[2][5], "Bring a pen");
+
+        // Writing the count back doesn't count as an edit.
+        set_attending(&mut data, &key, &fingerprint, "4".to_string()).unwrap();
+        assert_eq!(data[2][4], "4");
+
+        set_attending(
+            &mut data,
+            &social.key(),
+            &social.fingerprint(),
+            "1".to_string(),
+        )
+        .unwrap();
+        assert_eq!(data[3], vec!["Social", "07 Feb 2024", "The Pub", "", "1"]);
+    }
+
+    #[test]
+    fn test_set_attending_conflict() {
+        let mut data = sheet();
+        let workshop = Event::from_row(&data[2]).unwrap();
+        let (key, fingerprint) = (workshop.key(), workshop.fingerprint());
+
+        data[2][2] = "Main Hall".to_string();
+        assert_eq!(
+            set_attending(&mut data, &key, &fingerprint, "3".to_string()).unwrap_err(),
+            "Workshop was edited since it was announced."
+        );
+        assert_eq!(data[2][4], "");
+
+        data.remove(2);
+        assert!(set_attending(&mut data, &key, &fingerprint, "3".to_string()).is_err());
+    }
+
+    #[test]
+    fn test_add_event() {
+        let mut data = sheet();
+        let event = Event {
+            location: "Bookshop".to_string(),
+            notes: Some("Free entry".to_string()),
+            ..Event::new("Launch", "10 Feb 2024")
+        };
+
+        add_event(&mut data, &event).unwrap();
+        assert_eq!(
+            data[4],
+            vec![
+                "Launch",
+                "10 Feb 2024",
+                "Bookshop",
+                "",
+                "",
+                "Free entry",
+                ""
+            ]
+        );
+        assert!(add_event(&mut data, &event).is_err());
+
+        let mut data = Vec::new();
+        add_event(&mut data, &event).unwrap();
+        assert_eq!(data.len(), 3);
+    }
+
+    #[test]
+    fn test_date_string() {
+        let mut event = Event::from_row(&sheet()[2]).unwrap();
+        assert_eq!(
+            event.date_string(&chrono_tz::UTC),
+            "<t:1707220800:D> (<t:1707177600:R>)"
+        );
+        assert_eq!(
+            event.date_string(&chrono_tz::Australia::Sydney),
+            "<t:1707181200:D> (<t:1707138000:R>)"
+        );
+
+        event.date = "Early Feb".to_string();
+        assert_eq!(event.date_string(&chrono_tz::UTC), "Early Feb");
+    }
+}
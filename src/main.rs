@@ -12,10 +12,23 @@ use crate::{
 
 mod announcer;
 mod auth;
+mod autocomplete;
+mod changes;
+mod channels;
+mod clock;
+mod commands;
+mod components;
 mod consts;
+mod corrections;
 mod csv;
 mod discord;
+mod events;
+mod reminders;
 mod req;
+mod rsvp;
+mod scheduled;
+mod submissions;
+mod template;
 
 #[cfg(test)]
 mod test;
@@ -55,27 +68,77 @@ async fn register_commands() {
     struct ApplicationCommandRequest {
         name: String,
         description: String,
+        options: Vec<discord::ApplicationCommandOption>,
+        default_member_permissions: Option<String>,
 
         #[serde(rename = "type")]
         _type: i32,
     }
 
+    let organiser = Some(commands::ORGANISER_PERMISSIONS.to_string());
+
     let reqs = vec![
         ApplicationCommandRequest {
             name: "announce".to_string(),
             description: "Enable announcing in this channel.".to_string(),
+            options: channels::ChannelSetting::options(),
+            default_member_permissions: organiser.clone(),
             _type: ApplicationCommandType::ChatInput.ordinal(),
         },
         ApplicationCommandRequest {
             name: "cancel".to_string(),
             description: "Disable announcing in this channel.".to_string(),
+            options: Vec::new(),
+            default_member_permissions: organiser.clone(),
+            _type: ApplicationCommandType::ChatInput.ordinal(),
+        },
+        ApplicationCommandRequest {
+            name: "events".to_string(),
+            description: "List upcoming events.".to_string(),
+            options: commands::events_options(),
+            default_member_permissions: None,
+            _type: ApplicationCommandType::ChatInput.ordinal(),
+        },
+        ApplicationCommandRequest {
+            name: "event".to_string(),
+            description: "Propose an event.".to_string(),
+            options: submissions::event_options(),
+            default_member_permissions: None,
+            _type: ApplicationCommandType::ChatInput.ordinal(),
+        },
+        ApplicationCommandRequest {
+            name: "attendees".to_string(),
+            description: "List who has responded to an event.".to_string(),
+            options: rsvp::attendees_options(),
+            default_member_permissions: organiser.clone(),
+            _type: ApplicationCommandType::ChatInput.ordinal(),
+        },
+        ApplicationCommandRequest {
+            name: "template".to_string(),
+            description: "Change how this channel's digest is worded.".to_string(),
+            options: Vec::new(),
+            default_member_permissions: organiser.clone(),
+            _type: ApplicationCommandType::ChatInput.ordinal(),
+        },
+        ApplicationCommandRequest {
+            name: "category".to_string(),
+            description: "Style a category's heading in this channel's digest.".to_string(),
+            options: commands::category_options(),
+            default_member_permissions: organiser.clone(),
+            _type: ApplicationCommandType::ChatInput.ordinal(),
+        },
+        ApplicationCommandRequest {
+            name: "preview".to_string(),
+            description: "Preview this channel's next digest.".to_string(),
+            options: Vec::new(),
+            default_member_permissions: organiser.clone(),
             _type: ApplicationCommandType::ChatInput.ordinal(),
         },
     ];
 
     for req in reqs {
         match req::post::<&str, ApplicationCommandRequest, ApplicationCommand>(
-            &api_uri(&format!("/applications/{APPLICATION_ID}/commands")),
+            &api_uri(format!("/applications/{APPLICATION_ID}/commands")),
             req,
         )
         .await
@@ -91,6 +154,7 @@ async fn interactions(
     req: HttpRequest,
     body: web::Bytes,
     commands: web::Data<UnboundedSender<announcer::AnnouncerCommand>>,
+    clock: web::Data<dyn clock::Clock>,
 ) -> std::result::Result<web::Json<discord::InteractionResponse>, actix_web::Error> {
     let body = String::from_utf8(body.to_vec()).map_err(e422)?;
     let sighex = extract_header(&req, "X-Signature-Ed25519").map_err(e400)?;
@@ -105,16 +169,50 @@ async fn interactions(
     let resp = match interaction.inttype() {
         InteractionType::ApplicationCommand => match (interaction.channel(), interaction.command())
         {
+            (_, Some("events")) => commands::events(&interaction, clock.as_ref()).await,
+            (_, Some("event")) => submissions::event(&interaction),
+            (
+                Some(_),
+                Some("announce" | "cancel" | "attendees" | "template" | "category" | "preview"),
+            ) if !commands::is_organiser(&interaction) => discord::InteractionResponse::message(
+                "You need the Manage Channels permission or the organiser role to do that.",
+            )
+            .ephemeral(),
             (Some(channel), Some("announce")) => {
-                commands
-                    .send(announcer::AnnouncerCommand::RegisterChannel(
-                        channel.clone(),
-                    ))
-                    .ok();
-                discord::InteractionResponse::message(
-                    "Announcements will be sent in this channel every Sunday morning.",
-                )
+                match channels::ChannelSetting::from_interaction(&interaction) {
+                    Ok(settings) => {
+                        commands
+                            .send(announcer::AnnouncerCommand::RegisterChannel(
+                                channel.clone(),
+                                settings,
+                            ))
+                            .ok();
+                        discord::InteractionResponse::message(
+                            "Announcements will be sent in this channel every Sunday morning.",
+                        )
+                        .ephemeral()
+                    }
+                    Err(e) => discord::InteractionResponse::message(e).ephemeral(),
+                }
             }
+            (Some(_), Some("attendees")) => rsvp::attendees(&interaction, clock.as_ref()),
+            (Some(_), Some("template")) => commands::template(&interaction).await,
+            (Some(channel), Some("category")) => match commands::category(&interaction).await {
+                Ok(setting) => {
+                    commands
+                        .send(announcer::AnnouncerCommand::UpdateChannel(
+                            channel.clone(),
+                            vec![setting],
+                        ))
+                        .ok();
+                    discord::InteractionResponse::message(
+                        "Updated the category's style. Use /announce group_by_category to list events by category.",
+                    )
+                    .ephemeral()
+                }
+                Err(e) => discord::InteractionResponse::message(e).ephemeral(),
+            },
+            (Some(_), Some("preview")) => commands::preview(&interaction, clock.as_ref()).await,
             (Some(channel), Some("cancel")) => {
                 commands
                     .send(announcer::AnnouncerCommand::UnregisterChannel(
@@ -124,12 +222,47 @@ async fn interactions(
                 discord::InteractionResponse::message(
                     "Announcements will no longer be sent in this channel.",
                 )
+                .ephemeral()
             }
             (None, _) => {
                 discord::InteractionResponse::message("Use this command in a server channel.")
+                    .ephemeral()
             }
-            _ => discord::InteractionResponse::message("Unrecognised command."),
+            _ => discord::InteractionResponse::message("Unrecognised command.").ephemeral(),
         },
+        // Saving a template needs the announcer, so isn't routed with the
+        // other components.
+        InteractionType::ModalSubmit
+            if interaction.custom_id() == Some(commands::TEMPLATE_ROUTE) =>
+        {
+            match (interaction.channel(), commands::is_organiser(&interaction)) {
+                (Some(channel), true) => {
+                    match commands::template_from_modal(&interaction, &clock.now()) {
+                        Ok(template) => {
+                            commands
+                                .send(announcer::AnnouncerCommand::UpdateChannel(
+                                    channel.clone(),
+                                    vec![channels::ChannelSetting::Template(template)],
+                                ))
+                                .ok();
+                            discord::InteractionResponse::message(
+                                "Saved the template. Use /preview to see how it looks.",
+                            )
+                            .ephemeral()
+                        }
+                        Err(e) => discord::InteractionResponse::message(e).ephemeral(),
+                    }
+                }
+                _ => discord::InteractionResponse::message("Only organisers can edit templates.")
+                    .ephemeral(),
+            }
+        }
+        InteractionType::MessageComponent | InteractionType::ModalSubmit => {
+            components::dispatch(&interaction).await
+        }
+        InteractionType::ApplicationCommandAutocomplete => {
+            autocomplete::respond(&interaction).await
+        }
         InteractionType::Ping => discord::InteractionResponse::pong(),
         _ => return Err(e422("unhandled interaction type")),
     };
@@ -144,14 +277,17 @@ async fn main() -> std::io::Result<()> {
     register_commands().await;
 
     let (send, recv) = tokio::sync::mpsc::unbounded_channel();
-    announcer::run_announcer(recv).await;
+    let clock: std::sync::Arc<dyn clock::Clock> = std::sync::Arc::new(clock::SystemClock::new());
+    announcer::run_announcer(recv, clock.clone()).await;
 
     actix_web::HttpServer::new(move || {
         let commands = web::Data::new(send.clone());
+        let clock = web::Data::from(clock.clone());
 
         actix_web::App::new()
             .wrap(actix_web::middleware::Logger::default())
             .app_data(commands)
+            .app_data(clock)
             .service(interactions)
     })
     .bind(("0.0.0.0", 8080))?
@@ -0,0 +1,261 @@
+use std::sync::Arc;
+
+use chrono::DateTime;
+use chrono_tz::Tz;
+use tokio::sync::Mutex;
+
+use crate::{
+    announcer::send_embed,
+    channels::ChannelConfig,
+    clock::Clock,
+    csv::{load_csv, write_csv},
+    discord,
+    events::{load_announcements, Event},
+};
+
+const REMINDERS_CSV: &str = "reminders.csv";
+
+/// How often to reload events when no reminder is due sooner, so that changes
+/// to the sheet are noticed.
+const POLL_MINUTES: i64 = 60;
+
+/// Record of a reminder which has been sent for an event in a channel.
+#[derive(Debug, PartialEq)]
+struct SentReminder {
+    channel: discord::Snowflake,
+
+    /// Key of the event.
+    event: String,
+}
+
+impl SentReminder {
+    fn new(channel: &ChannelConfig, event: &Event) -> Self {
+        Self {
+            channel: channel.id.clone(),
+            event: event.key(),
+        }
+    }
+
+    fn is_for(&self, event: &Event) -> bool {
+        self.event == event.key()
+    }
+
+    fn from_row(row: Vec<String>) -> Option<Self> {
+        let mut cols = row.into_iter();
+        Some(Self {
+            channel: cols.next()?,
+            event: cols.next()?,
+        })
+    }
+
+    fn to_row(&self) -> Vec<String> {
+        vec![self.channel.clone(), self.event.clone()]
+    }
+}
+
+async fn load_sent() -> Vec<SentReminder> {
+    let csv = load_csv(REMINDERS_CSV).await.unwrap_or_default();
+
+    csv.into_iter().filter_map(SentReminder::from_row).collect()
+}
+
+async fn save_sent(sent: &[SentReminder]) {
+    if let Err(e) = write_csv(
+        &sent.iter().map(SentReminder::to_row).collect(),
+        REMINDERS_CSV,
+    )
+    .await
+    {
+        eprintln!("{e}");
+    }
+}
+
+/// When the reminder for an event should be posted in a channel.
+fn remind_at(channel: &ChannelConfig, event: &Event, tz: &Tz) -> Option<DateTime<Tz>> {
+    let hours = channel.reminder_hours?;
+    if !channel.filter.matches(event) {
+        return None;
+    }
+    let start = event.start_time(tz)?;
+    Some(start - chrono::Duration::hours(hours.into()))
+}
+
+/// Reminders which should be posted at `now` and haven't already been sent.
+/// Events which have already started aren't worth reminding anyone about.
+fn due_reminders<'a>(
+    channels: &'a [ChannelConfig],
+    events: &'a [Event],
+    sent: &[SentReminder],
+    now: &DateTime<Tz>,
+) -> Vec<(&'a ChannelConfig, &'a Event)> {
+    let tz = now.timezone();
+    let mut due = Vec::new();
+    for channel in channels {
+        for event in events {
+            let (Some(at), Some(start)) = (remind_at(channel, event, &tz), event.start_time(&tz))
+            else {
+                continue;
+            };
+
+            if at <= *now
+                && *now < start
+                && !sent
+                    .iter()
+                    .any(|r| r.channel == channel.id && r.is_for(event))
+            {
+                due.push((channel, event));
+            }
+        }
+    }
+    due
+}
+
+/// The first reminder due after `now`.
+fn next_reminder(
+    channels: &[ChannelConfig],
+    events: &[Event],
+    now: &DateTime<Tz>,
+) -> Option<DateTime<Tz>> {
+    let tz = now.timezone();
+    channels
+        .iter()
+        .flat_map(|channel| events.iter().map(move |event| (channel, event)))
+        .filter_map(|(channel, event)| remind_at(channel, event, &tz))
+        .filter(|at| at > now)
+        .min()
+}
+
+fn reminder_embed(event: &Event, tz: &Tz) -> discord::Embed {
+    let embed = discord::Embed::new(format!("Coming up: {}", event.name), event.summary(tz));
+    match &event.link {
+        Some(link) => embed.url(link),
+        None => embed,
+    }
+}
+
+/// Post any reminders which are due, returning when to next check.
+async fn send_reminders(channels: &[ChannelConfig], now: &DateTime<Tz>) -> DateTime<Tz> {
+    let poll = *now + chrono::Duration::minutes(POLL_MINUTES);
+    if channels.iter().all(|c| c.reminder_hours.is_none()) {
+        return poll;
+    }
+
+    let events = match load_announcements().await {
+        Ok(events) => events,
+        Err(e) => {
+            eprintln!("Failed to load events: {e}");
+            return poll;
+        }
+    };
+
+    let tz = now.timezone();
+    let mut sent = load_sent().await;
+
+    // Forget reminders for events which have started or been removed.
+    sent.retain(|r| {
+        events
+            .iter()
+            .any(|e| r.is_for(e) && e.start_time(&tz).is_some_and(|start| start > *now))
+    });
+
+    for (channel, event) in due_reminders(channels, &events, &sent, now) {
+        match send_embed(reminder_embed(event, &tz), &channel.id).await {
+            Ok(_) => sent.push(SentReminder::new(channel, event)),
+            Err(e) => eprintln!("{e}"),
+        }
+    }
+    save_sent(&sent).await;
+
+    next_reminder(channels, &events, now).map_or(poll, |next| next.min(poll))
+}
+
+/// Post reminders ahead of events in channels which have enabled them.
+pub async fn run_reminders(channels: Arc<Mutex<Vec<ChannelConfig>>>, clock: Arc<dyn Clock>) {
+    loop {
+        let registered = channels.lock().await.clone();
+        let next = send_reminders(&registered, &clock.now()).await;
+        clock.sleep_until(next).await;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use chrono::{DateTime, TimeZone};
+    use chrono_tz::{Australia::Sydney, Tz};
+
+    use crate::{channels::ChannelConfig, events::Event};
+
+    use super::{due_reminders, next_reminder, SentReminder};
+
+    fn at(y: i32, m: u32, d: u32, h: u32, min: u32) -> DateTime<Tz> {
+        Sydney
+            .with_ymd_and_hms(y, m, d, h, min, 0)
+            .single()
+            .unwrap()
+    }
+
+    fn channel(id: &str, hours: Option<u32>) -> ChannelConfig {
+        let mut channel = ChannelConfig::new(id.to_string());
+        channel.reminder_hours = hours;
+        channel
+    }
+
+    #[test]
+    fn test_due_reminders() {
+        let channels = vec![
+            channel("1", Some(24)),
+            channel("2", Some(2)),
+            channel("3", None),
+        ];
+        let events = vec![
+            Event::new("Wednesday", "07 Feb 2024"),
+            Event::new("Friday", "09 Feb 2024"),
+        ];
+
+        let due = due_reminders(&channels, &events, &[], &at(2024, 2, 6, 0, 0));
+        assert_eq!(due.len(), 1);
+        assert_eq!(due[0].0.id, "1");
+        assert_eq!(due[0].1.name, "Wednesday");
+
+        let due = due_reminders(&channels, &events, &[], &at(2024, 2, 6, 23, 0));
+        assert_eq!(due.len(), 2);
+        assert_eq!(due[1].0.id, "2");
+
+        // Already started.
+        assert!(due_reminders(&channels, &events, &[], &at(2024, 2, 7, 0, 0)).is_empty());
+    }
+
+    #[test]
+    fn test_sent_reminders_skipped() {
+        let channels = vec![channel("1", Some(24))];
+        let events = vec![Event::new("Wednesday", "07 Feb 2024")];
+        let sent = vec![SentReminder::new(&channels[0], &events[0])];
+        assert!(due_reminders(&channels, &events, &sent, &at(2024, 2, 6, 12, 0)).is_empty());
+        assert_eq!(
+            SentReminder::from_row(sent[0].to_row()).as_ref(),
+            sent.first()
+        );
+    }
+
+    #[test]
+    fn test_next_reminder() {
+        let channels = vec![channel("1", Some(24)), channel("2", Some(2))];
+        let events = vec![
+            Event::new("Wednesday", "07 Feb 2024"),
+            Event::new("Friday", "09 Feb 2024"),
+        ];
+
+        assert_eq!(
+            next_reminder(&channels, &events, &at(2024, 2, 1, 0, 0)),
+            Some(at(2024, 2, 6, 0, 0))
+        );
+        assert_eq!(
+            next_reminder(&channels, &events, &at(2024, 2, 6, 0, 0)),
+            Some(at(2024, 2, 6, 22, 0))
+        );
+        assert_eq!(
+            next_reminder(&channels, &events, &at(2024, 2, 8, 22, 0)),
+            None
+        );
+    }
+}
@@ -14,13 +14,42 @@ pub async fn get<U: AsRef<str>>(uri: U) -> Result<String> {
 }
 
 pub async fn post<U: AsRef<str>, S: Serialize, D: DeserializeOwned>(uri: U, body: S) -> Result<D> {
-    let uri = uri.as_ref();
+    request(reqwest::Method::POST, uri.as_ref(), body).await
+}
+
+pub async fn patch<U: AsRef<str>, S: Serialize, D: DeserializeOwned>(uri: U, body: S) -> Result<D> {
+    request(reqwest::Method::PATCH, uri.as_ref(), body).await
+}
+
+pub async fn delete<U: AsRef<str>>(uri: U) -> Result<()> {
+    let resp = reqwest::Client::new()
+        .delete(uri.as_ref())
+        .header("Authorization", TOKEN)
+        .send()
+        .await
+        .map_err(msg)?;
+    if resp.status().is_success() {
+        return Ok(());
+    }
+
+    let bytes = resp.bytes().await.map_err(msg)?;
+    match serde_json::from_slice::<ErrorResponse>(&bytes) {
+        Ok(err_resp) => Err(err_resp.message),
+        Err(e) => Err(format!("Failed to deserialise response: {e}")),
+    }
+}
+
+async fn request<S: Serialize, D: DeserializeOwned>(
+    method: reqwest::Method,
+    uri: &str,
+    body: S,
+) -> Result<D> {
     let body =
         serde_json::to_vec(&body).map_err(|_| format!("Failed to serialise request to {uri}"))?;
 
     let client = reqwest::Client::new();
     let res = client
-        .post(uri)
+        .request(method, uri)
         .header("Authorization", TOKEN)
         .header("Content-Type", "application/json")
         .body(body)
@@ -0,0 +1,526 @@
+use chrono::DateTime;
+use chrono_tz::Tz;
+use tokio::sync::Mutex;
+
+use crate::{
+    clock::Clock,
+    csv::{load_csv, write_csv},
+    discord::{self, ButtonStyle, MessageComponent},
+    events::{self, load_announcements, Event},
+};
+
+const RSVPS_CSV: &str = "rsvps.csv";
+
+/// Route of RSVP button custom ids, which are
+/// `rsvp:{event key}:{event fingerprint}:{status}`.
+pub const ROUTE: &str = "rsvp";
+
+/// Precedes the counts appended to an event's field in the digest.
+const COUNTS_PREFIX: &str = "\nRSVPs: ";
+
+/// Most characters the counts line adds to a field, allowing for up to five
+/// digits in each count.
+pub const MAX_COUNTS_LENGTH: usize =
+    COUNTS_PREFIX.len() + "99999 going, 99999 maybe, 99999 not going".len();
+
+/// Discord's limit on button labels.
+const MAX_LABEL_LENGTH: usize = 80;
+
+/// Held while reading and rewriting the RSVPs file, so that concurrent clicks
+/// aren't lost.
+static LOCK: Mutex<()> = Mutex::const_new(());
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Rsvp {
+    Going,
+    Maybe,
+    NotGoing,
+}
+
+impl Rsvp {
+    const ALL: [Rsvp; 3] = [Self::Going, Self::Maybe, Self::NotGoing];
+
+    fn as_str(&self) -> &'static str {
+        match self {
+            Self::Going => "going",
+            Self::Maybe => "maybe",
+            Self::NotGoing => "not_going",
+        }
+    }
+
+    fn parse(text: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|rsvp| rsvp.as_str() == text)
+    }
+
+    fn label(&self) -> &'static str {
+        match self {
+            Self::Going => "Going",
+            Self::Maybe => "Maybe",
+            Self::NotGoing => "Not going",
+        }
+    }
+
+    fn style(&self) -> ButtonStyle {
+        match self {
+            Self::Going => ButtonStyle::Success,
+            Self::Maybe => ButtonStyle::Primary,
+            Self::NotGoing => ButtonStyle::Secondary,
+        }
+    }
+}
+
+/// A user's response to an event, identified by its key.
+#[derive(Debug, PartialEq)]
+struct Response {
+    event: String,
+    user: discord::Snowflake,
+    rsvp: Rsvp,
+}
+
+impl Response {
+    fn from_row(row: Vec<String>) -> Option<Self> {
+        let mut cols = row.into_iter();
+        Some(Self {
+            event: cols.next()?,
+            user: cols.next()?,
+            rsvp: Rsvp::parse(&cols.next()?)?,
+        })
+    }
+
+    fn to_row(&self) -> Vec<String> {
+        vec![
+            self.event.clone(),
+            self.user.clone(),
+            self.rsvp.as_str().to_string(),
+        ]
+    }
+}
+
+async fn load_responses() -> Vec<Response> {
+    let csv = load_csv(RSVPS_CSV).await.unwrap_or_default();
+
+    csv.into_iter().filter_map(Response::from_row).collect()
+}
+
+async fn save_responses(responses: &[Response]) {
+    if let Err(e) = write_csv(&responses.iter().map(Response::to_row).collect(), RSVPS_CSV).await {
+        eprintln!("{e}");
+    }
+}
+
+/// Number of users who gave each response to an event.
+#[derive(Debug, Default, PartialEq)]
+struct Counts {
+    going: usize,
+    maybe: usize,
+    not_going: usize,
+}
+
+impl Counts {
+    fn new(responses: &[Response], event: &str) -> Self {
+        let mut counts = Self::default();
+        for response in responses.iter().filter(|r| r.event == event) {
+            match response.rsvp {
+                Rsvp::Going => counts.going += 1,
+                Rsvp::Maybe => counts.maybe += 1,
+                Rsvp::NotGoing => counts.not_going += 1,
+            }
+        }
+        counts
+    }
+}
+
+impl std::fmt::Display for Counts {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} going, {} maybe, {} not going",
+            self.going, self.maybe, self.not_going
+        )
+    }
+}
+
+/// Replace a user's response to an event.
+fn set_response(responses: &mut Vec<Response>, event: &str, user: &str, rsvp: Rsvp) {
+    match responses
+        .iter_mut()
+        .find(|r| r.event == event && r.user == user)
+    {
+        Some(response) => response.rsvp = rsvp,
+        None => responses.push(Response {
+            event: event.to_string(),
+            user: user.to_string(),
+            rsvp,
+        }),
+    }
+}
+
+/// Record a user's response to an event, returning the updated counts.
+async fn record(event: &str, user: &str, rsvp: Rsvp) -> Counts {
+    let _lock = LOCK.lock().await;
+    let mut responses = load_responses().await;
+    set_response(&mut responses, event, user, rsvp);
+    save_responses(&responses).await;
+    Counts::new(&responses, event)
+}
+
+fn custom_id(event: &Event, rsvp: Rsvp) -> String {
+    format!(
+        "{ROUTE}:{}:{}:{}",
+        event.key(),
+        event.fingerprint(),
+        rsvp.as_str()
+    )
+}
+
+/// A row of RSVP buttons for each event. The rows line up with the events'
+/// fields in the digest, so callers should split events into pages of at most
+/// Discord's limit on rows; any beyond it are reported and left without
+/// buttons.
+pub fn buttons(events: &[Event]) -> Vec<MessageComponent> {
+    if let Some(extra) = events.get(MessageComponent::MAX_ROWS..) {
+        let names: Vec<&str> = extra.iter().map(|e| e.name.as_str()).collect();
+        eprintln!("No room for RSVP buttons for {}", names.join(", "));
+    }
+
+    events
+        .iter()
+        .take(MessageComponent::MAX_ROWS)
+        .map(|event| {
+            let key = event.key();
+            let name: String = event.name.chars().take(MAX_LABEL_LENGTH).collect();
+            let mut row = vec![MessageComponent::button(
+                ButtonStyle::Secondary,
+                name,
+                format!("{ROUTE}:{key}"),
+            )
+            .disabled(true)];
+            row.extend(Rsvp::ALL.into_iter().map(|rsvp| {
+                MessageComponent::button(
+                    rsvp.style(),
+                    rsvp.label().to_string(),
+                    custom_id(event, rsvp),
+                )
+            }));
+            MessageComponent::action_row(row)
+        })
+        .collect()
+}
+
+/// Field value with its counts line replaced.
+fn with_counts(value: &str, counts: &Counts) -> String {
+    let value = value
+        .rsplit_once(COUNTS_PREFIX)
+        .map_or(value, |(value, _)| value);
+    let counts = format!("{COUNTS_PREFIX}{counts}");
+    let value = discord::truncate(
+        value.to_string(),
+        discord::Embed::MAX_FIELD_VALUE_LENGTH - counts.chars().count(),
+    );
+    format!("{value}{counts}")
+}
+
+/// Add the counts for events with responses to their fields, for when a
+/// digest page is posted again. Events' fields are in order across the
+/// embeds.
+pub async fn add_counts(embeds: &mut [discord::Embed], events: &[Event]) {
+    let responses = {
+        let _lock = LOCK.lock().await;
+        load_responses().await
+    };
+    for (field, event) in embeds
+        .iter_mut()
+        .flat_map(|embed| embed.fields_mut().iter_mut())
+        .zip(events.iter().take(MessageComponent::MAX_ROWS))
+    {
+        let counts = Counts::new(&responses, &event.key());
+        if counts != Counts::default() {
+            field.value = with_counts(&field.value, &counts);
+        }
+    }
+}
+
+/// The message's embeds, with counts for the event updated in the field
+/// corresponding to the event's row of buttons, counting fields in order
+/// across the embeds.
+fn updated_embeds(
+    message: &discord::Message,
+    event: &str,
+    counts: &Counts,
+) -> Option<Vec<discord::Embed>> {
+    let prefix = format!("{ROUTE}:{event}:");
+    let row = message.components().iter().position(|row| {
+        row.components()
+            .iter()
+            .any(|button| button.custom_id().is_some_and(|id| id.starts_with(&prefix)))
+    })?;
+
+    let mut embeds = message.embeds().to_vec();
+    let field = embeds
+        .iter_mut()
+        .flat_map(|embed| embed.fields_mut().iter_mut())
+        .nth(row)?;
+    field.value = with_counts(&field.value, counts);
+    Some(embeds)
+}
+
+/// Copy the number of users going to the events source, if it's writable and
+/// the event hasn't been edited since it was announced. The count is taken
+/// once the events file is locked, so that a slow write can't replace a later
+/// one with an older count.
+async fn write_back(event: String, fingerprint: String) {
+    if !events::writable() {
+        return;
+    }
+
+    let going = async {
+        let _lock = LOCK.lock().await;
+        Counts::new(&load_responses().await, &event)
+            .going
+            .to_string()
+    };
+    if let Err(e) = events::update_attending(&event, &fingerprint, going).await {
+        eprintln!("Failed to update attending: {e}");
+    }
+}
+
+/// Handle a click on an RSVP button, with arguments
+/// `{event key}:{event fingerprint}:{status}`.
+pub async fn handle(
+    interaction: &discord::Interaction,
+    args: &str,
+) -> discord::InteractionResponse {
+    let response = args
+        .rsplit_once(':')
+        .and_then(|(event, rsvp)| Some((event, Rsvp::parse(rsvp)?)));
+    let (Some((event, rsvp)), Some(user)) = (response, interaction.user()) else {
+        return discord::InteractionResponse::message("Unrecognised response.").ephemeral();
+    };
+
+    // Buttons posted before fingerprints were added can't be checked for
+    // edits, so aren't written back.
+    let (event, fingerprint) = match event.split_once(':') {
+        Some((event, fingerprint)) => (event, Some(fingerprint)),
+        None => (event, None),
+    };
+    let counts = record(event, &user.id, rsvp).await;
+    if let Some(fingerprint) = fingerprint {
+        tokio::spawn(write_back(event.to_string(), fingerprint.to_string()));
+    }
+    let message = interaction.message();
+    match message.and_then(|m| updated_embeds(m, event, &counts)) {
+        Some(embeds) => discord::InteractionResponse::update(
+            embeds,
+            message.map(|m| m.components().to_vec()).unwrap_or_default(),
+        ),
+        None => {
+            discord::InteractionResponse::message(format!("Recorded: {}", rsvp.label())).ephemeral()
+        }
+    }
+}
+
+pub fn attendees_options() -> Vec<discord::ApplicationCommandOption> {
+    vec![discord::ApplicationCommandOption::new(
+        discord::ApplicationCommandOptionType::String,
+        "event",
+        "Name of the event.",
+    )
+    .required(true)
+    .autocomplete(true)]
+}
+
+/// List of users who responded to the event, grouped by response.
+fn attendee_list(event: &Event, responses: &[Response], tz: &Tz) -> String {
+    let key = event.key();
+    let mut list = format!("**{}**, {}", event.name, event.date_string(tz));
+    for rsvp in Rsvp::ALL {
+        let users: Vec<String> = responses
+            .iter()
+            .filter(|r| r.event == key && r.rsvp == rsvp)
+            .map(|r| format!("<@{}>", r.user))
+            .collect();
+        list.push_str(&format!(
+            "\n{} ({}): {}",
+            rsvp.label(),
+            users.len(),
+            users.join(", ")
+        ));
+    }
+    list
+}
+
+/// The event with the given name, preferring the next one to start.
+fn find_event<'a>(events: &'a [Event], name: &str, now: &DateTime<Tz>) -> Option<&'a Event> {
+    let tz = now.timezone();
+    let mut matching: Vec<&Event> = events
+        .iter()
+        .filter(|e| e.name.trim().eq_ignore_ascii_case(name.trim()))
+        .collect();
+    matching.sort_by_key(|e| e.start_time(&tz));
+    matching
+        .iter()
+        .find(|e| e.start_time(&tz).is_some_and(|start| start >= *now))
+        .or(matching.last())
+        .copied()
+}
+
+/// Who has responded to the named event, or why they couldn't be listed.
+async fn attendees_reply(name: &str, now: &DateTime<Tz>) -> String {
+    const MAX_CONTENT_LENGTH: usize = 2000;
+
+    let events = match load_announcements().await {
+        Ok(events) => events,
+        Err(e) => {
+            eprintln!("Failed to load events: {e}");
+            return "Failed to load events, please try again later.".to_string();
+        }
+    };
+    let Some(event) = find_event(&events, name, now) else {
+        return format!("No event named {name}.");
+    };
+
+    let responses = load_responses().await;
+    discord::truncate(
+        attendee_list(event, &responses, &now.timezone()),
+        MAX_CONTENT_LENGTH,
+    )
+}
+
+/// Reply to an organiser with who has responded to an event. The events sheet
+/// may be slow to load, so the response is always deferred.
+pub fn attendees(
+    interaction: &discord::Interaction,
+    clock: &dyn Clock,
+) -> discord::InteractionResponse {
+    let name = interaction
+        .option_str("event")
+        .unwrap_or_default()
+        .to_string();
+    let now = clock.now();
+    interaction.defer(discord::MessageFlags::EPHEMERAL, async move {
+        discord::WebhookMessage::content(attendees_reply(&name, &now).await)
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{discord, events::Event};
+
+    use super::{
+        attendee_list, buttons, set_response, updated_embeds, with_counts, Counts, Response, Rsvp,
+    };
+
+    #[test]
+    fn test_responses() {
+        let mut responses = Vec::new();
+        set_response(&mut responses, "a", "1", Rsvp::Going);
+        set_response(&mut responses, "a", "2", Rsvp::Maybe);
+        set_response(&mut responses, "b", "1", Rsvp::NotGoing);
+        set_response(&mut responses, "a", "2", Rsvp::Going);
+
+        assert_eq!(
+            Counts::new(&responses, "a"),
+            Counts {
+                going: 2,
+                maybe: 0,
+                not_going: 0
+            }
+        );
+        assert_eq!(
+            Counts::new(&responses, "b").to_string(),
+            "0 going, 0 maybe, 1 not going"
+        );
+        assert_eq!(
+            Response::from_row(responses[2].to_row()).as_ref(),
+            responses.get(2)
+        );
+        assert_eq!(
+            Response::from_row(vec!["a".into(), "1".into(), "yes".into()]),
+            None
+        );
+    }
+
+    #[test]
+    fn test_event_key() {
+        let workshop = Event::new("Workshop", "06 Feb 2024");
+        assert_eq!(workshop.key(), Event::new("Workshop", "06 Feb 2024").key());
+        assert_ne!(workshop.key(), Event::new("Workshop", "13 Feb 2024").key());
+        assert_eq!(workshop.key().len(), 16);
+    }
+
+    #[test]
+    fn test_with_counts() {
+        let counts = Counts {
+            going: 1,
+            ..Default::default()
+        };
+        let value = with_counts("Tuesday, Library.", &counts);
+        assert_eq!(
+            value,
+            "Tuesday, Library.\nRSVPs: 1 going, 0 maybe, 0 not going"
+        );
+        assert_eq!(
+            with_counts(&value, &Counts::default()),
+            "Tuesday, Library.\nRSVPs: 0 going, 0 maybe, 0 not going"
+        );
+    }
+
+    #[test]
+    fn test_updated_embeds() {
+        let events = vec![
+            Event::new("Workshop", "06 Feb 2024"),
+            Event::new("Social", "07 Feb 2024"),
+        ];
+        // Rows of buttons line up with fields across the embeds, as when a
+        // digest is grouped by category.
+        let mut embed = discord::Embed::new("Events", "This week");
+        embed.add_field("Workshop".to_string(), "Tuesday".to_string());
+        let mut socials = discord::Embed::new("Socials", "");
+        socials.add_field("Social".to_string(), "Wednesday".to_string());
+
+        let message: discord::Message = serde_json::from_value(serde_json::json!({
+            "id": "1",
+            "channel_id": "2",
+            "author": { "id": "3", "username": "bot" },
+            "content": "",
+            "timestamp": "2024-02-04T09:00:00+00:00",
+            "edited_timestamp": null,
+            "tts": false,
+            "mention_everyone": false,
+            "mentions": [],
+            "mention_roles": [],
+            "attachments": [],
+            "embeds": [embed, socials],
+            "pinned": false,
+            "type": 0,
+            "components": buttons(&events),
+        }))
+        .unwrap();
+
+        let counts = Counts {
+            maybe: 1,
+            ..Default::default()
+        };
+        let embeds = updated_embeds(&message, &events[1].key(), &counts).unwrap();
+        assert_eq!(embeds[0].fields()[0].value, "Tuesday");
+        assert_eq!(
+            embeds[1].fields()[0].value,
+            "Wednesday\nRSVPs: 0 going, 1 maybe, 0 not going"
+        );
+        assert!(updated_embeds(&message, "unknown", &counts).is_none());
+
+        let list = attendee_list(
+            &events[0],
+            &[Response {
+                event: events[0].key(),
+                user: "4".to_string(),
+                rsvp: Rsvp::Going,
+            }],
+            &chrono_tz::UTC,
+        );
+        assert_eq!(
+            list,
+            "**Workshop**, <t:1707220800:D> (<t:1707177600:R>)\nGoing (1): <@4>\nMaybe (0): \nNot going (0): "
+        );
+    }
+}
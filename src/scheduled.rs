@@ -0,0 +1,429 @@
+use std::sync::Arc;
+
+use chrono::DateTime;
+use chrono_tz::Tz;
+use serde::Serialize;
+
+use crate::{
+    clock::{timezone, Clock},
+    consts::SCHEDULED_EVENTS_GUILD,
+    csv::{load_csv, write_csv},
+    discord::{
+        self, GuildScheduledEventEntityMetadata, GuildScheduledEventEntityType,
+        GuildScheduledEventPrivacyLevel,
+    },
+    events::{load_announcements, stable_hash, Event},
+    req, Result,
+};
+
+const SCHEDULED_CSV: &str = "scheduled.csv";
+
+/// How often to mirror the events into the server.
+const POLL_MINUTES: i64 = 60;
+
+/// Discord's limits on scheduled events.
+const MAX_NAME_LENGTH: usize = 100;
+const MAX_DESCRIPTION_LENGTH: usize = 1000;
+const MAX_LOCATION_LENGTH: usize = 100;
+
+/// A scheduled event created for an event, stored as a row of the scheduled
+/// CSV so that the sync can tell which event it mirrors.
+#[derive(Clone, Debug, PartialEq)]
+struct Mirror {
+    id: discord::Snowflake,
+
+    /// Key of the event.
+    event: String,
+
+    /// Name of the event, to follow it if it moves.
+    name: String,
+
+    /// When the scheduled event starts, so that it can be told to have
+    /// started even if the event has since been removed.
+    start: DateTime<Tz>,
+
+    /// Hash of the request the scheduled event was last saved with.
+    fingerprint: String,
+}
+
+impl Mirror {
+    fn new(id: discord::Snowflake, event: &Event, request: &ScheduledEventRequest) -> Self {
+        Self {
+            id,
+            event: event.key(),
+            name: event.name.clone(),
+            start: request.start,
+            fingerprint: request.fingerprint(),
+        }
+    }
+
+    fn is_for(&self, event: &Event) -> bool {
+        self.event == event.key()
+    }
+
+    fn from_row(row: Vec<String>) -> Option<Self> {
+        let mut cols = row.into_iter();
+        Some(Self {
+            id: cols.next()?,
+            event: cols.next()?,
+            name: cols.next()?,
+            start: DateTime::parse_from_rfc3339(&cols.next()?)
+                .ok()?
+                .with_timezone(&timezone()),
+            fingerprint: cols.next()?,
+        })
+    }
+
+    fn to_row(&self) -> Vec<String> {
+        vec![
+            self.id.clone(),
+            self.event.clone(),
+            self.name.clone(),
+            self.start.to_rfc3339(),
+            self.fingerprint.clone(),
+        ]
+    }
+}
+
+async fn load_mirrors() -> Vec<Mirror> {
+    let csv = load_csv(SCHEDULED_CSV).await.unwrap_or_default();
+
+    csv.into_iter().filter_map(Mirror::from_row).collect()
+}
+
+async fn save_mirrors(mirrors: &[Mirror]) {
+    if let Err(e) = write_csv(&mirrors.iter().map(Mirror::to_row).collect(), SCHEDULED_CSV).await {
+        eprintln!("{e}");
+    }
+}
+
+#[derive(Serialize)]
+struct ScheduledEventRequest {
+    #[serde(skip)]
+    start: DateTime<Tz>,
+
+    name: String,
+    privacy_level: i32,
+    scheduled_start_time: String,
+    scheduled_end_time: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    description: Option<String>,
+    entity_type: i32,
+    entity_metadata: GuildScheduledEventEntityMetadata,
+}
+
+impl ScheduledEventRequest {
+    /// External scheduled event at the event's location. Events only have
+    /// dates, so they're scheduled for the whole day.
+    fn new(event: &Event, tz: &Tz) -> Option<Self> {
+        let start = event.start_time(tz)?;
+        let end = start + chrono::Duration::days(1);
+
+        let mut description: Vec<&str> = Vec::new();
+        description.extend(event.notes.as_deref());
+        description.extend(event.link.as_deref());
+        let location = match event.location.trim() {
+            "" => "TBA".to_string(),
+            location => location.to_string(),
+        };
+
+        Some(Self {
+            start,
+            name: discord::truncate(event.name.clone(), MAX_NAME_LENGTH),
+            privacy_level: GuildScheduledEventPrivacyLevel::GuildOnly.ordinal(),
+            scheduled_start_time: start.to_rfc3339(),
+            scheduled_end_time: end.to_rfc3339(),
+            description: (!description.is_empty())
+                .then(|| discord::truncate(description.join("\n"), MAX_DESCRIPTION_LENGTH)),
+            entity_type: GuildScheduledEventEntityType::External.ordinal(),
+            entity_metadata: GuildScheduledEventEntityMetadata {
+                location: Some(discord::truncate(location, MAX_LOCATION_LENGTH)),
+            },
+        })
+    }
+
+    fn fingerprint(&self) -> String {
+        stable_hash(&serde_json::to_string(self).unwrap_or_default())
+    }
+}
+
+/// How to bring the server's scheduled events in line with the events.
+#[derive(Debug, PartialEq)]
+enum Action<'a> {
+    Keep(&'a Mirror),
+    Create(&'a Event),
+    Update(&'a Mirror, &'a Event),
+    Delete(&'a Mirror),
+
+    /// Stop tracking a scheduled event which has started, leaving Discord to
+    /// end it.
+    Forget(&'a Mirror),
+}
+
+/// Actions to mirror the upcoming events. An event is matched to the
+/// scheduled event created for it by its key or, if it has moved, to the
+/// upcoming one of the same name with the nearest start, so that interest in
+/// it is kept.
+fn plan<'a>(mirrors: &'a [Mirror], events: &'a [Event], now: &DateTime<Tz>) -> Vec<Action<'a>> {
+    let tz = now.timezone();
+    let upcoming: Vec<(&Event, ScheduledEventRequest)> = events
+        .iter()
+        .filter(|e| e.start_time(&tz).is_some_and(|start| start > *now))
+        .filter_map(|e| ScheduledEventRequest::new(e, &tz).map(|request| (e, request)))
+        .collect();
+
+    let mut unmatched: Vec<Option<&Mirror>> = mirrors.iter().map(Some).collect();
+    let mut take = |matches: &dyn Fn(&Mirror) -> bool| {
+        unmatched
+            .iter_mut()
+            .find(|m| m.is_some_and(matches))
+            .and_then(Option::take)
+    };
+
+    let mut actions = Vec::new();
+    let mut moved = Vec::new();
+    for (event, request) in &upcoming {
+        match take(&|m| m.is_for(event)) {
+            Some(mirror) if mirror.fingerprint == request.fingerprint() => {
+                actions.push(Action::Keep(mirror))
+            }
+            Some(mirror) => actions.push(Action::Update(mirror, event)),
+            None => moved.push((*event, request)),
+        }
+    }
+
+    // Scheduled events which have started are left alone, rather than moved
+    // to a later instance.
+    for (event, request) in moved {
+        let nearest = unmatched
+            .iter_mut()
+            .filter(|m| m.is_some_and(|m| m.name == event.name && m.start > *now))
+            .min_by_key(|m| m.map(|m| (m.start - request.start).num_seconds().abs()))
+            .and_then(Option::take);
+        match nearest {
+            Some(mirror) => actions.push(Action::Update(mirror, event)),
+            None => actions.push(Action::Create(event)),
+        }
+    }
+
+    // The mirror's own start is used, as a started event may have since been
+    // removed from the sheet.
+    for mirror in unmatched.into_iter().flatten() {
+        if mirror.start <= *now {
+            actions.push(Action::Forget(mirror));
+        } else {
+            actions.push(Action::Delete(mirror));
+        }
+    }
+    actions
+}
+
+fn scheduled_events_uri(guild: &str, id: Option<&str>) -> String {
+    match id {
+        Some(id) => req::api_uri(format!("/guilds/{guild}/scheduled-events/{id}")),
+        None => req::api_uri(format!("/guilds/{guild}/scheduled-events")),
+    }
+}
+
+/// Carry out an action, returning the mirror to keep, if any. Failed actions
+/// keep the existing mirror so that they're retried.
+async fn apply(guild: &str, action: Action<'_>, tz: &Tz) -> Result<Option<Mirror>> {
+    match action {
+        Action::Keep(mirror) => Ok(Some(mirror.clone())),
+        Action::Forget(_) => Ok(None),
+        Action::Delete(mirror) => req::delete(scheduled_events_uri(guild, Some(&mirror.id)))
+            .await
+            .map(|()| None),
+        Action::Create(event) => {
+            let Some(request) = ScheduledEventRequest::new(event, tz) else {
+                return Ok(None);
+            };
+            let created: discord::GuildScheduledEvent =
+                req::post(scheduled_events_uri(guild, None), &request).await?;
+            Ok(Some(Mirror::new(created.id().clone(), event, &request)))
+        }
+        Action::Update(mirror, event) => {
+            let Some(request) = ScheduledEventRequest::new(event, tz) else {
+                return Ok(Some(mirror.clone()));
+            };
+            let updated = Mirror::new(mirror.id.clone(), event, &request);
+            req::patch::<_, _, discord::GuildScheduledEvent>(
+                scheduled_events_uri(guild, Some(&mirror.id)),
+                request,
+            )
+            .await
+            .map(|_| Some(updated))
+        }
+    }
+}
+
+/// Create, update and delete scheduled events to mirror the upcoming events.
+async fn sync(guild: &str, now: &DateTime<Tz>) {
+    let events = match load_announcements().await {
+        Ok(events) => events,
+        Err(e) => {
+            eprintln!("Failed to load events: {e}");
+            return;
+        }
+    };
+
+    let mirrors = load_mirrors().await;
+    let actions = plan(&mirrors, &events, now);
+    let existing: Vec<Option<Mirror>> = actions
+        .iter()
+        .map(|action| match action {
+            Action::Keep(mirror)
+            | Action::Update(mirror, _)
+            | Action::Delete(mirror)
+            | Action::Forget(mirror) => Some((*mirror).clone()),
+            Action::Create(_) => None,
+        })
+        .collect();
+
+    let mut synced = Vec::new();
+    for (i, action) in actions.into_iter().enumerate() {
+        let create = matches!(action, Action::Create(_));
+        match apply(guild, action, &now.timezone()).await {
+            Ok(mirror) => synced.extend(mirror),
+            Err(e) => {
+                eprintln!("Failed to sync scheduled event: {e}");
+                synced.extend(existing[i].clone());
+            }
+        }
+
+        // Save each created event straight away, along with those yet to be
+        // synced, so that it isn't created again if the sync is interrupted.
+        if create {
+            let mut saved = synced.clone();
+            saved.extend(existing[i + 1..].iter().flatten().cloned());
+            save_mirrors(&saved).await;
+        }
+    }
+    save_mirrors(&synced).await;
+}
+
+/// Periodically mirror the events into the server's scheduled events, if
+/// enabled.
+pub async fn run_scheduled(clock: Arc<dyn Clock>) {
+    let Some(guild) = SCHEDULED_EVENTS_GUILD else {
+        return;
+    };
+
+    loop {
+        let now = clock.now();
+        sync(guild, &now).await;
+        clock
+            .sleep_until(now + chrono::Duration::minutes(POLL_MINUTES))
+            .await;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use chrono::TimeZone;
+
+    use crate::events::Event;
+
+    use super::{plan, Action, Mirror, ScheduledEventRequest};
+
+    fn event(name: &str, date: &str) -> Event {
+        Event {
+            notes: Some("Bring a pen".to_string()),
+            ..Event::new(name, date)
+        }
+    }
+
+    fn mirror(event: &Event) -> Mirror {
+        let request = ScheduledEventRequest::new(event, &chrono_tz::UTC).unwrap();
+        Mirror::new(format!("id {}", event.name), event, &request)
+    }
+
+    #[test]
+    fn test_request() {
+        let request =
+            ScheduledEventRequest::new(&event("Workshop", "06 Feb 2024"), &chrono_tz::UTC).unwrap();
+        assert_eq!(
+            serde_json::to_value(&request).unwrap(),
+            serde_json::json!({
+                "name": "Workshop",
+                "privacy_level": 2,
+                "scheduled_start_time": "2024-02-06T00:00:00+00:00",
+                "scheduled_end_time": "2024-02-07T00:00:00+00:00",
+                "description": "Bring a pen",
+                "entity_type": 3,
+                "entity_metadata": { "location": "Library" }
+            })
+        );
+        assert!(ScheduledEventRequest::new(&event("Workshop", "soon"), &chrono_tz::UTC).is_none());
+    }
+
+    #[test]
+    fn test_plan() {
+        let now = chrono_tz::UTC
+            .with_ymd_and_hms(2024, 2, 4, 9, 0, 0)
+            .unwrap();
+        let unchanged = event("Workshop", "06 Feb 2024");
+        let mut edited = event("Launch", "07 Feb 2024");
+        let moved = event("Social", "09 Feb 2024");
+        let new = event("Write-in", "10 Feb 2024");
+        let started = event("Retreat", "04 Feb 2024");
+
+        let mirrors = vec![
+            mirror(&unchanged),
+            mirror(&edited),
+            mirror(&event("Social", "08 Feb 2024")),
+            mirror(&event("Cancelled", "08 Feb 2024")),
+            mirror(&started),
+        ];
+        edited.location = "Bookshop".to_string();
+        let events = vec![unchanged, edited, moved, new, started];
+
+        assert_eq!(
+            plan(&mirrors, &events, &now),
+            vec![
+                Action::Keep(&mirrors[0]),
+                Action::Update(&mirrors[1], &events[1]),
+                Action::Update(&mirrors[2], &events[2]),
+                Action::Create(&events[3]),
+                Action::Delete(&mirrors[3]),
+                Action::Forget(&mirrors[4]),
+            ]
+        );
+        assert!(plan(&mirrors[..0], &[], &now).is_empty());
+
+        // An event which has started is forgotten even if it's been removed.
+        assert_eq!(
+            plan(&mirrors[4..], &[], &now),
+            vec![Action::Forget(&mirrors[4])]
+        );
+    }
+
+    #[test]
+    fn test_plan_recurring() {
+        let now = chrono_tz::UTC
+            .with_ymd_and_hms(2024, 2, 4, 9, 0, 0)
+            .unwrap();
+
+        // Today's instance has started, so isn't moved to next week's.
+        let mirrors = vec![mirror(&event("Write-in", "04 Feb 2024"))];
+        let events = vec![event("Write-in", "11 Feb 2024")];
+        assert_eq!(
+            plan(&mirrors, &events, &now),
+            vec![Action::Create(&events[0]), Action::Forget(&mirrors[0])]
+        );
+
+        // A moved instance takes over the nearest scheduled event.
+        let mirrors = vec![
+            mirror(&event("Write-in", "08 Feb 2024")),
+            mirror(&event("Write-in", "15 Feb 2024")),
+        ];
+        let events = vec![event("Write-in", "14 Feb 2024")];
+        assert_eq!(
+            plan(&mirrors, &events, &now),
+            vec![
+                Action::Update(&mirrors[1], &events[0]),
+                Action::Delete(&mirrors[0]),
+            ]
+        );
+    }
+}
@@ -0,0 +1,369 @@
+use tokio::sync::Mutex;
+
+use crate::{
+    announcer::{send_dm, send_message, CreateMessageRequest},
+    clock::timezone,
+    commands::is_organiser,
+    consts::MODERATION_CHANNEL,
+    csv::{load_csv, write_csv},
+    discord::{self, ButtonStyle, MessageComponent, TextInputStyle},
+    err,
+    events::{self, Event},
+    Result,
+};
+
+const PENDING_CSV: &str = "pending.csv";
+
+/// Custom id of the event submission modal.
+pub const ROUTE: &str = "event";
+
+/// Route of approve and reject buttons, which are `review:{event key}:{verdict}`.
+pub const REVIEW_ROUTE: &str = "review";
+
+const APPROVE: &str = "approve";
+const REJECT: &str = "reject";
+
+const NAME: &str = "name";
+const DATE: &str = "date";
+const LOCATION: &str = "location";
+const CATEGORY: &str = "category";
+const NOTES: &str = "notes";
+
+/// Held while reading and rewriting the pending events file.
+static PENDING_LOCK: Mutex<()> = Mutex::const_new(());
+
+/// An event awaiting approval, with the user who submitted it so that they
+/// can be told of the outcome.
+#[derive(Debug)]
+struct Submission {
+    event: Event,
+    submitter: discord::Snowflake,
+}
+
+impl Submission {
+    /// Columns of the event's row in the events sheet.
+    const EVENT_COLS: usize = 7;
+
+    fn from_row(row: Vec<String>) -> Option<Self> {
+        let event = Event::from_row(row.get(..Self::EVENT_COLS)?)?;
+        let submitter = row.into_iter().nth(Self::EVENT_COLS)?;
+        Some(Self { event, submitter })
+    }
+
+    fn to_row(&self) -> Vec<String> {
+        let mut row = self.event.to_row();
+        row.push(self.submitter.clone());
+        row
+    }
+
+    fn embed(&self, title: &str) -> discord::Embed {
+        let mut embed = discord::Embed::new(
+            format!("{title}: {}", self.event.name),
+            self.event.summary(&timezone()),
+        );
+        if let Some(category) = &self.event.category {
+            embed.add_field("Category".to_string(), category.clone());
+        }
+        embed.add_field("Submitted by".to_string(), format!("<@{}>", self.submitter));
+        embed
+    }
+}
+
+async fn load_pending() -> Vec<Submission> {
+    let csv = load_csv(PENDING_CSV).await.unwrap_or_default();
+
+    csv.into_iter().filter_map(Submission::from_row).collect()
+}
+
+async fn save_pending(pending: &[Submission]) {
+    if let Err(e) = write_csv(
+        &pending.iter().map(Submission::to_row).collect(),
+        PENDING_CSV,
+    )
+    .await
+    {
+        eprintln!("{e}");
+    }
+}
+
+pub fn event_options() -> Vec<discord::ApplicationCommandOption> {
+    vec![discord::ApplicationCommandOption::new(
+        discord::ApplicationCommandOptionType::SubCommand,
+        "add",
+        "Propose a new event.",
+    )]
+}
+
+/// Form for the details of a new event.
+fn modal() -> discord::InteractionResponse {
+    discord::InteractionResponse::modal(
+        ROUTE,
+        "Add an Event",
+        vec![
+            MessageComponent::text_input(TextInputStyle::Short, NAME, "Name")
+                .length(1, 100)
+                .required(true),
+            MessageComponent::text_input(TextInputStyle::Short, DATE, "Date")
+                .placeholder("06 Feb 2024")
+                .required(true),
+            MessageComponent::text_input(TextInputStyle::Short, LOCATION, "Location")
+                .length(1, 100)
+                .required(true),
+            MessageComponent::text_input(TextInputStyle::Short, CATEGORY, "Category")
+                .required(false),
+            MessageComponent::text_input(TextInputStyle::Paragraph, NOTES, "Notes")
+                .length(0, 500)
+                .required(false),
+        ],
+    )
+}
+
+/// Handle the event command, opening the submission form.
+pub fn event(interaction: &discord::Interaction) -> discord::InteractionResponse {
+    match interaction.subcommand() {
+        Some("add") if events::writable() => modal(),
+        Some("add") => {
+            discord::InteractionResponse::message("Events can't be added from Discord.").ephemeral()
+        }
+        _ => discord::InteractionResponse::message("Unrecognised command.").ephemeral(),
+    }
+}
+
+/// Event from a submitted form, if its details are valid.
+fn event_from_modal(interaction: &discord::Interaction) -> Result<Event> {
+    let text = |id| {
+        interaction
+            .text_value(id)
+            .map(str::trim)
+            .filter(|text| !text.is_empty())
+            .map(str::to_string)
+    };
+
+    let (Some(name), Some(date), Some(location)) = (text(NAME), text(DATE), text(LOCATION)) else {
+        return err("Events need a name, date and location.");
+    };
+
+    let event = Event {
+        name,
+        date,
+        location,
+        category: text(CATEGORY),
+        attending: None,
+        notes: text(NOTES),
+        link: None,
+    };
+    if event.start_time(&timezone()).is_none() {
+        return err(format!(
+            "Couldn't read the date {}, please use a format like 06 Feb 2024.",
+            event.date
+        ));
+    }
+    Ok(event)
+}
+
+/// Add an event straight to the events source.
+async fn add(event: Event) -> String {
+    match events::append_event(&event).await {
+        Ok(()) => format!(
+            "Added {} on {}.",
+            event.name,
+            event.date_string(&timezone())
+        ),
+        Err(e) => e,
+    }
+}
+
+/// Queue an event for approval, posting it to the moderation channel.
+async fn submit(interaction: &discord::Interaction, event: Event, channel: &str) -> String {
+    let Some(submitter) = interaction.user() else {
+        return "Couldn't tell who submitted the event.".to_string();
+    };
+    let submission = Submission {
+        event,
+        submitter: submitter.id.clone(),
+    };
+    let key = submission.event.key();
+
+    let _lock = PENDING_LOCK.lock().await;
+    let mut pending = load_pending().await;
+    if pending.iter().any(|p| p.event.key() == key) {
+        return format!(
+            "{} on {} is already awaiting approval.",
+            submission.event.name, submission.event.date
+        );
+    }
+
+    let buttons = vec![MessageComponent::action_row(vec![
+        MessageComponent::button(
+            ButtonStyle::Success,
+            "Approve".to_string(),
+            format!("{REVIEW_ROUTE}:{key}:{APPROVE}"),
+        ),
+        MessageComponent::button(
+            ButtonStyle::Danger,
+            "Reject".to_string(),
+            format!("{REVIEW_ROUTE}:{key}:{REJECT}"),
+        ),
+    ])];
+    let body = CreateMessageRequest {
+        embeds: Some(vec![submission.embed("Submitted")]),
+        components: Some(buttons),
+        ..Default::default()
+    };
+    if let Err(e) = send_message(body, &channel.to_string()).await {
+        eprintln!("Failed to post submission: {e}");
+        return "Failed to submit the event, please try again later.".to_string();
+    }
+
+    let reply = format!(
+        "Thanks! {} will be listed once an organiser approves it.",
+        submission.event.name
+    );
+    pending.push(submission);
+    save_pending(&pending).await;
+    reply
+}
+
+/// Handle a submitted event form, adding the event to the events source or
+/// queueing it for approval.
+pub async fn handle(
+    interaction: &discord::Interaction,
+    _args: &str,
+) -> discord::InteractionResponse {
+    let reply = match event_from_modal(interaction) {
+        Ok(event) => match MODERATION_CHANNEL {
+            Some(channel) => submit(interaction, event, channel).await,
+            None => add(event).await,
+        },
+        Err(e) => e,
+    };
+    discord::InteractionResponse::message(reply).ephemeral()
+}
+
+/// Handle an organiser approving or rejecting a submission, with arguments
+/// `{event key}:{verdict}`.
+pub async fn review(
+    interaction: &discord::Interaction,
+    args: &str,
+) -> discord::InteractionResponse {
+    if !is_organiser(interaction) {
+        return discord::InteractionResponse::message("Only organisers can review events.")
+            .ephemeral();
+    }
+    let Some((key, approve)) = args
+        .split_once(':')
+        .and_then(|(key, verdict)| match verdict {
+            APPROVE => Some((key, true)),
+            REJECT => Some((key, false)),
+            _ => None,
+        })
+    else {
+        return discord::InteractionResponse::message("Unrecognised review.").ephemeral();
+    };
+
+    let _lock = PENDING_LOCK.lock().await;
+    let mut pending = load_pending().await;
+    let Some(index) = pending.iter().position(|p| p.event.key() == key) else {
+        return discord::InteractionResponse::message("This event has already been reviewed.")
+            .ephemeral();
+    };
+    if approve {
+        if let Err(e) = events::append_event(&pending[index].event).await {
+            return discord::InteractionResponse::message(e).ephemeral();
+        }
+    }
+    let submission = pending.remove(index);
+    save_pending(&pending).await;
+
+    let mut embed = submission.embed(if approve { "Approved" } else { "Rejected" });
+    if let Some(reviewer) = interaction.user() {
+        embed.add_field("Reviewed by".to_string(), format!("<@{}>", reviewer.id));
+    }
+
+    // Reviews can come long after the submission's interaction token has
+    // expired, so the submitter is told privately by direct message.
+    if !approve {
+        let body = CreateMessageRequest {
+            content: Some(format!(
+                "Sorry, {} on {} wasn't approved by the organisers.",
+                submission.event.name, submission.event.date
+            )),
+            allowed_mentions: Some(discord::AllowedMentions::none()),
+            ..Default::default()
+        };
+        tokio::spawn(async move {
+            if let Err(e) = send_dm(body, &submission.submitter).await {
+                eprintln!("Failed to notify submitter: {e}");
+            }
+        });
+    }
+
+    discord::InteractionResponse::update(vec![embed], Vec::new())
+}
+
+#[cfg(test)]
+mod test {
+    use crate::discord::Interaction;
+
+    use super::{event_from_modal, Submission};
+
+    fn submission(date: &str, notes: &str) -> Interaction {
+        let input = |id: &str, value: &str| {
+            serde_json::json!({
+                "type": 1,
+                "components": [{ "type": 4, "custom_id": id, "value": value }]
+            })
+        };
+        serde_json::from_value(serde_json::json!({
+            "type": 5,
+            "id": "1",
+            "application_id": "2",
+            "token": "token",
+            "version": 1,
+            "entitlements": [],
+            "data": {
+                "custom_id": "event",
+                "components": [
+                    input("name", " Launch "),
+                    input("date", date),
+                    input("location", "Bookshop"),
+                    input("category", ""),
+                    input("notes", notes),
+                ]
+            }
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_event_from_modal() {
+        let event = event_from_modal(&submission("10 Feb 2024", "Free entry")).unwrap();
+        assert_eq!(event.name, "Launch");
+        assert_eq!(event.location, "Bookshop");
+        assert_eq!(event.category, None);
+        assert_eq!(event.notes.as_deref(), Some("Free entry"));
+
+        assert!(event_from_modal(&submission("10-11 Feb 2024", "")).is_ok());
+        assert_eq!(
+            event_from_modal(&submission("next Friday", "")).unwrap_err(),
+            "Couldn't read the date next Friday, please use a format like 06 Feb 2024."
+        );
+    }
+
+    #[test]
+    fn test_submission_row() {
+        let interaction = submission("10 Feb 2024", "Free entry");
+        let submission = Submission {
+            event: event_from_modal(&interaction).unwrap(),
+            submitter: "3".to_string(),
+        };
+
+        let row = submission.to_row();
+        assert_eq!(row[7..], ["3"]);
+
+        let loaded = Submission::from_row(row).unwrap();
+        assert_eq!(loaded.event, submission.event);
+        assert_eq!(loaded.submitter, "3");
+        assert!(Submission::from_row(vec!["Launch".to_string()]).is_none());
+    }
+}
@@ -0,0 +1,225 @@
+use chrono::DateTime;
+use chrono_tz::Tz;
+use minijinja::{Environment, UndefinedBehavior};
+use serde::Serialize;
+
+use crate::{channels::Window, events::Event, msg, Result};
+
+const DATE_FORMAT: &str = "%A %d/%m";
+
+/// How a channel's digest is worded, as minijinja templates. The title,
+/// description and empty message may use `window`, `start`, `end` and
+/// `count`; the field templates may use `name`, `date`, `location`,
+/// `category`, `notes` and `link`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Template {
+    pub title: String,
+    pub description: String,
+    pub field_name: String,
+    pub field_value: String,
+
+    /// Posted instead of a digest when there are no events.
+    pub empty: String,
+}
+
+impl Default for Template {
+    fn default() -> Self {
+        Self {
+            title: "{{ window }}".to_string(),
+            description: "{{ start }} through {{ end }}".to_string(),
+            field_name: "{{ name }}".to_string(),
+            field_value: concat!(
+                "{{ date }}, {{ location }}",
+                "{% if notes %}. {{ notes }}{% endif %}",
+                "{% if link %}\n[Details]({{ link }}){% endif %}"
+            )
+            .to_string(),
+            empty: "No events are on from {{ start }} through {{ end }}.".to_string(),
+        }
+    }
+}
+
+/// Values available to the title, description and empty message.
+#[derive(Serialize)]
+pub struct DigestVars {
+    window: String,
+    start: String,
+    end: String,
+    count: usize,
+}
+
+impl DigestVars {
+    pub fn new(window: &Window, now: &DateTime<Tz>, count: usize) -> Option<Self> {
+        let end = window.last_day(now.date_naive())?;
+        Some(Self {
+            window: window.title(),
+            start: now.format(DATE_FORMAT).to_string(),
+            end: end.format(DATE_FORMAT).to_string(),
+            count,
+        })
+    }
+}
+
+/// Values available to the field templates.
+#[derive(Serialize)]
+struct EventVars<'a> {
+    name: &'a str,
+    date: String,
+    location: &'a str,
+    category: Option<&'a str>,
+    notes: Option<&'a str>,
+    link: Option<&'a str>,
+}
+
+impl<'a> EventVars<'a> {
+    fn new(event: &'a Event, tz: &Tz) -> Self {
+        Self {
+            name: &event.name,
+            date: event.date_string(tz),
+            location: &event.location,
+            category: event.category.as_deref(),
+            notes: event.notes.as_deref(),
+            link: event.link.as_deref(),
+        }
+    }
+}
+
+fn render<S: Serialize>(source: &str, vars: &S) -> Result<String> {
+    let mut env = Environment::new();
+    // Catch misspelt variables when the template is saved.
+    env.set_undefined_behavior(UndefinedBehavior::Strict);
+    env.render_str(source, vars).map_err(msg)
+}
+
+/// Render with the template, falling back to the default if it fails, e.g.
+/// because the channels file was edited by hand.
+fn render_or<S: Serialize>(source: &str, default: &str, vars: &S) -> String {
+    render(source, vars)
+        .or_else(|e| {
+            eprintln!("Failed to render template: {e}");
+            render(default, vars)
+        })
+        .unwrap_or_default()
+}
+
+impl Template {
+    pub fn title(&self, vars: &DigestVars) -> String {
+        render_or(&self.title, &Self::default().title, vars)
+    }
+
+    pub fn description(&self, vars: &DigestVars) -> String {
+        render_or(&self.description, &Self::default().description, vars)
+    }
+
+    /// Name and value of the field for an event.
+    pub fn field(&self, event: &Event, tz: &Tz) -> (String, String) {
+        let vars = EventVars::new(event, tz);
+        let default = Self::default();
+        (
+            render_or(&self.field_name, &default.field_name, &vars),
+            render_or(&self.field_value, &default.field_value, &vars),
+        )
+    }
+
+    pub fn empty(&self, vars: &DigestVars) -> String {
+        render_or(&self.empty, &Self::default().empty, vars)
+    }
+
+    /// Check that each template renders, so that mistakes are reported when
+    /// the template is saved rather than when the digest is posted.
+    pub fn validate(&self, now: &DateTime<Tz>) -> Result<()> {
+        let digest = DigestVars::new(&Window::default(), now, 1)
+            .ok_or_else(|| msg("Failed to check template."))?;
+        let event = Event {
+            name: "Event".to_string(),
+            date: now.format("%d %b %Y").to_string(),
+            location: "Location".to_string(),
+            category: Some("Category".to_string()),
+            attending: None,
+            notes: Some("Notes".to_string()),
+            link: Some("https://example.com".to_string()),
+        };
+        let event = EventVars::new(&event, &now.timezone());
+
+        let check = |label: &str, result: Result<String>| {
+            result.map_err(|e| format!("Error in {label} template: {e}"))
+        };
+        check("title", render(&self.title, &digest))?;
+        check("description", render(&self.description, &digest))?;
+        check("field name", render(&self.field_name, &event))?;
+        check("field value", render(&self.field_value, &event))?;
+        check("empty week", render(&self.empty, &digest))?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use chrono::TimeZone;
+
+    use crate::{channels::Window, events::Event};
+
+    use super::{DigestVars, Template};
+
+    fn event(notes: Option<&str>) -> Event {
+        Event {
+            notes: notes.map(str::to_string),
+            ..Event::new("Workshop", "Early Feb")
+        }
+    }
+
+    #[test]
+    fn test_default_template() {
+        let now = chrono_tz::UTC
+            .with_ymd_and_hms(2024, 2, 4, 9, 0, 0)
+            .unwrap();
+        let template = Template::default();
+        let vars = DigestVars::new(&Window::default(), &now, 2).unwrap();
+
+        assert_eq!(template.title(&vars), "Events this Week");
+        assert_eq!(
+            template.description(&vars),
+            "Sunday 04/02 through Sunday 11/02"
+        );
+        assert_eq!(
+            template.field(&event(None), &chrono_tz::UTC),
+            ("Workshop".to_string(), "Early Feb, Library".to_string())
+        );
+        assert_eq!(
+            template
+                .field(&event(Some("Bring a pen")), &chrono_tz::UTC)
+                .1,
+            "Early Feb, Library. Bring a pen"
+        );
+        assert!(template.validate(&now).is_ok());
+    }
+
+    #[test]
+    fn test_custom_template() {
+        let now = chrono_tz::UTC
+            .with_ymd_and_hms(2024, 2, 4, 9, 0, 0)
+            .unwrap();
+        let vars = DigestVars::new(&Window::Days(3), &now, 2).unwrap();
+        let mut template = Template {
+            title: "{{ count }} things to write home about".to_string(),
+            field_name: "✍️ {{ name | upper }}".to_string(),
+            ..Template::default()
+        };
+
+        assert_eq!(template.title(&vars), "2 things to write home about");
+        assert_eq!(
+            template.field(&event(None), &chrono_tz::UTC).0,
+            "✍️ WORKSHOP"
+        );
+
+        template.field_value = "{{ venue }}".to_string();
+        assert!(template
+            .validate(&now)
+            .unwrap_err()
+            .starts_with("Error in field value template"));
+        assert_eq!(
+            template.field(&event(None), &chrono_tz::UTC).1,
+            "Early Feb, Library"
+        );
+    }
+}